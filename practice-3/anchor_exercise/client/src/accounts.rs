@@ -0,0 +1,61 @@
+use anchor_lang::{AccountDeserialize, Result};
+use approve::{BasketOffer, Config, MakerState, Market, Offer};
+
+/// Deserializes the data of an offer account
+///
+/// # Errors
+///
+/// Fails if the data is not an `Offer` account.
+pub fn deserialize_offer(data: &[u8]) -> Result<Offer> {
+    deserialize(data)
+}
+
+/// Deserializes the data of a basket offer account
+///
+/// # Errors
+///
+/// Fails if the data is not a `BasketOffer` account.
+pub fn deserialize_basket_offer(data: &[u8]) -> Result<BasketOffer> {
+    deserialize(data)
+}
+
+/// Deserializes the data of a maker state account
+///
+/// # Errors
+///
+/// Fails if the data is not a `MakerState` account.
+pub fn deserialize_maker_state(data: &[u8]) -> Result<MakerState> {
+    deserialize(data)
+}
+
+/// Deserializes the data of the config account
+///
+/// # Errors
+///
+/// Fails if the data is not the `Config` account.
+pub fn deserialize_config(data: &[u8]) -> Result<Config> {
+    deserialize(data)
+}
+
+/// Deserializes the data of a market account
+///
+/// # Errors
+///
+/// Fails if the data is not a `Market` account.
+pub fn deserialize_market(data: &[u8]) -> Result<Market> {
+    deserialize(data)
+}
+
+/// Returns the id of the maker's next offer, `maker_state` is the data of
+/// its maker state account or `None` before its first offer
+///
+/// # Errors
+///
+/// Fails if the data is not a `MakerState` account.
+pub fn next_offer_id(maker_state: Option<&[u8]>) -> Result<u64> {
+    maker_state.map_or(Ok(0), |data| Ok(deserialize_maker_state(data)?.offer_nonce))
+}
+
+fn deserialize<T: AccountDeserialize>(mut data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut data)
+}
@@ -0,0 +1,198 @@
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        system_program,
+    },
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token;
+use approve::{instructions::shared::is_native_mint, Offer, OfferMode};
+
+use crate::pda::{config_address, maker_state_address, offer_address, token_account_address, vault_address};
+
+/// Terms of a new offer
+///
+/// [`OfferTerms::new`] makes a public delegation mode offer that is not
+/// listed on a market.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OfferTerms {
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    pub expires_at: i64, // unix timestamp
+    pub mode: OfferMode,
+    pub allowed_taker: Option<Pubkey>,
+    pub allowlist_root: Option<[u8; 32]>,
+    pub market: Option<Pubkey>, // market whose order book lists the offer, vault offers only
+}
+
+impl OfferTerms {
+    pub fn new(token_a_amount: u64, token_b_amount: u64, expires_at: i64) -> Self {
+        Self {
+            token_a_amount,
+            token_b_amount,
+            expires_at,
+            mode: OfferMode::Delegation,
+            allowed_taker: None,
+            allowlist_root: None,
+            market: None,
+        }
+    }
+}
+
+/// Builds `make_offer` for the maker's offer `id`, which must be the offer
+/// nonce of its maker state, see [`crate::next_offer_id`]
+///
+/// Accounts required by a transfer hook of token A are appended by the caller.
+pub fn make_offer_ix(
+    maker: &Pubkey,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    id: u64,
+    terms: &OfferTerms,
+    token_program_a: &Pubkey,
+    token_program_b: &Pubkey,
+) -> Instruction {
+    let offer = offer_address(maker, id);
+    let vault = (terms.mode == OfferMode::Vault).then(|| vault_address(&offer));
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::MakeOffer {
+            maker: *maker,
+            token_mint_a: *mint_a,
+            token_mint_b: *mint_b,
+            maker_token_account_a: token_account_address(maker, mint_a, token_program_a),
+            config: config_address(),
+            maker_state: maker_state_address(maker),
+            offer,
+            vault,
+            market: terms.market,
+            associated_token_program: associated_token::ID,
+            token_program_a: *token_program_a,
+            token_program_b: *token_program_b,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::MakeOffer {
+            token_a_offered_amount: terms.token_a_amount,
+            token_b_wanted_amount: terms.token_b_amount,
+            expires_at: terms.expires_at,
+            mode: terms.mode,
+            allowed_taker: terms.allowed_taker,
+            allowlist_root: terms.allowlist_root,
+        }
+        .data(),
+    }
+}
+
+/// Accounts of `take_offer` and `take_offer_partial` for `offer`
+///
+/// The vault, the market and the token B accounts are passed according to
+/// the offer, `treasury` is the treasury of the config.
+pub fn take_offer_accounts(
+    taker: &Pubkey,
+    offer: &Offer,
+    treasury: &Pubkey,
+    token_program_a: &Pubkey,
+    token_program_b: &Pubkey,
+) -> Vec<AccountMeta> {
+    let offer_key = offer_address(&offer.maker, offer.id);
+    let mint_a = &offer.token_mint_a;
+    let mint_b = &offer.token_mint_b;
+    let token_b_account = |owner: &Pubkey| {
+        (!is_native_mint(mint_b)).then(|| token_account_address(owner, mint_b, token_program_b))
+    };
+
+    approve::accounts::TakeOffer {
+        taker: *taker,
+        maker: offer.maker,
+        token_mint_a: *mint_a,
+        token_mint_b: *mint_b,
+        maker_token_account_a: token_account_address(&offer.maker, mint_a, token_program_a),
+        taker_token_account_a: token_account_address(taker, mint_a, token_program_a),
+        taker_token_account_b: token_b_account(taker),
+        maker_token_account_b: token_b_account(&offer.maker),
+        offer: offer_key,
+        vault: (offer.mode == OfferMode::Vault).then(|| vault_address(&offer_key)),
+        config: config_address(),
+        treasury: *treasury,
+        treasury_token_account_b: token_b_account(treasury),
+        market: offer.market,
+        associated_token_program: associated_token::ID,
+        token_program_a: *token_program_a,
+        token_program_b: *token_program_b,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None)
+}
+
+/// Builds `take_offer` filling what is left of `offer`
+///
+/// `allowlist_proof` is empty unless the offer has an allowlist. Accounts
+/// required by transfer hooks are appended by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn take_offer_ix(
+    taker: &Pubkey,
+    offer: &Offer,
+    treasury: &Pubkey,
+    expected_token_a_amount: u64,
+    max_token_b_amount: u64,
+    allowlist_proof: Vec<[u8; 32]>,
+    token_program_a: &Pubkey,
+    token_program_b: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: take_offer_accounts(taker, offer, treasury, token_program_a, token_program_b),
+        data: approve::instruction::TakeOffer {
+            expected_token_a_amount,
+            max_token_b_amount,
+            allowlist_proof,
+        }
+        .data(),
+    }
+}
+
+/// Builds `take_offer_partial` paying `token_b_amount` for part of `offer`,
+/// see [`take_offer_ix`]
+#[allow(clippy::too_many_arguments)]
+pub fn take_offer_partial_ix(
+    taker: &Pubkey,
+    offer: &Offer,
+    treasury: &Pubkey,
+    token_b_amount: u64,
+    expected_token_a_amount: u64,
+    allowlist_proof: Vec<[u8; 32]>,
+    token_program_a: &Pubkey,
+    token_program_b: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: take_offer_accounts(taker, offer, treasury, token_program_a, token_program_b),
+        data: approve::instruction::TakeOfferPartial {
+            token_b_amount,
+            expected_token_a_amount,
+            allowlist_proof,
+        }
+        .data(),
+    }
+}
+
+/// Builds `cancel_offer` for `offer`, signed by its maker
+pub fn cancel_offer_ix(offer: &Offer, token_program_a: &Pubkey) -> Instruction {
+    let offer_key = offer_address(&offer.maker, offer.id);
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::CancelOffer {
+            maker: offer.maker,
+            token_mint_a: offer.token_mint_a,
+            maker_token_account_a: token_account_address(&offer.maker, &offer.token_mint_a, token_program_a),
+            offer: offer_key,
+            vault: (offer.mode == OfferMode::Vault).then(|| vault_address(&offer_key)),
+            market: offer.market,
+            token_program: *token_program_a,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::CancelOffer {}.data(),
+    }
+}
@@ -0,0 +1,16 @@
+//! Client helpers for the approve program
+//!
+//! Builds the program's instructions from the account and argument types
+//! generated by Anchor, derives the addresses of its accounts and
+//! deserializes them, so callers do not have to repeat the seeds and
+//! associated token accounts the program expects.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::*;
+pub use instructions::*;
+pub use pda::*;
+
+pub use approve::{self, Config, MakerState, Market, Offer, OfferMode, ID};
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use approve::{Market, SEED};
+
+/// Address of the global program state
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[SEED.as_bytes()], &approve::ID).0
+}
+
+/// Address of the maker's state, whose offer nonce is the id of its next offer
+pub fn maker_state_address(maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"maker", maker.as_ref()], &approve::ID).0
+}
+
+/// Address of the maker's offer `id`
+pub fn offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer", maker.as_ref(), &id.to_le_bytes()], &approve::ID).0
+}
+
+/// Address of the maker's basket offer `id`
+pub fn basket_offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"basket", maker.as_ref(), &id.to_le_bytes()], &approve::ID).0
+}
+
+/// Address of the market of a pair, the mints can be passed in either order
+pub fn market_address(mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    let (base_mint, quote_mint) = Market::canonical_pair(*mint_a, *mint_b);
+    Pubkey::find_program_address(
+        &[b"market", base_mint.as_ref(), quote_mint.as_ref()],
+        &approve::ID,
+    )
+    .0
+}
+
+/// Associated token account of `owner` for `mint` owned by `token_program`
+pub fn token_account_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+/// Vault PDA token account holding token A of a vault mode offer
+pub fn vault_address(offer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", offer.as_ref()], &approve::ID).0
+}
@@ -0,0 +1,107 @@
+use anchor_lang::{prelude::Pubkey, AccountSerialize};
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use approve_client::*;
+
+fn offer(mode: OfferMode, token_mint_b: Pubkey) -> Offer {
+    Offer {
+        id: 7,
+        maker: Pubkey::new_unique(),
+        token_mint_a: Pubkey::new_unique(),
+        token_mint_b,
+        token_a_amount: 10,
+        token_b_wanted_amount: 20,
+        remaining_a: 10,
+        remaining_b: 20,
+        expires_at: 1_700_000_000,
+        mode,
+        allowed_taker: None,
+        allowlist_root: None,
+        market: None,
+        bump: 255,
+    }
+}
+
+fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+fn has_account(instruction: &anchor_lang::solana_program::instruction::Instruction, key: &Pubkey) -> bool {
+    instruction.accounts.iter().any(|meta| meta.pubkey == *key)
+}
+
+#[test]
+fn make_offer_derives_the_offer_from_the_id_and_passes_the_vault_only_in_vault_mode() {
+    let maker = Pubkey::new_unique();
+    let mint_a = Pubkey::new_unique();
+    let mint_b = Pubkey::new_unique();
+    let offer = offer_address(&maker, 3);
+    let vault = vault_address(&offer);
+
+    let mut terms = OfferTerms::new(10, 20, 1_700_000_000);
+    let delegation = make_offer_ix(&maker, &mint_a, &mint_b, 3, &terms, &spl_token_2022::ID, &spl_token::ID);
+    terms.mode = OfferMode::Vault;
+    let vault_mode = make_offer_ix(&maker, &mint_a, &mint_b, 3, &terms, &spl_token_2022::ID, &spl_token::ID);
+
+    assert!(has_account(&delegation, &offer));
+    assert!(has_account(&delegation, &maker_state_address(&maker)));
+    assert!(!has_account(&delegation, &vault));
+    assert!(has_account(&vault_mode, &vault));
+    assert_eq!(delegation.accounts.len(), vault_mode.accounts.len());
+}
+
+#[test]
+fn take_offer_omits_token_b_accounts_for_sol_offers() {
+    let taker = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let token_offer = offer(OfferMode::Delegation, Pubkey::new_unique());
+    let sol_offer = offer(OfferMode::Delegation, spl_token::native_mint::ID);
+
+    let token_take = take_offer_ix(&taker, &token_offer, &treasury, 10, 20, Vec::new(), &spl_token::ID, &spl_token::ID);
+    let sol_take = take_offer_ix(&taker, &sol_offer, &treasury, 10, 20, Vec::new(), &spl_token::ID, &spl_token::ID);
+
+    let treasury_account_b = token_account_address(&treasury, &token_offer.token_mint_b, &spl_token::ID);
+    assert!(has_account(&token_take, &treasury_account_b));
+    assert!(has_account(&token_take, &offer_address(&token_offer.maker, token_offer.id)));
+    let treasury_account_sol = token_account_address(&treasury, &sol_offer.token_mint_b, &spl_token::ID);
+    assert!(!has_account(&sol_take, &treasury_account_sol));
+    assert_eq!(token_take.accounts.len(), sol_take.accounts.len());
+}
+
+#[test]
+fn cancel_offer_passes_the_vault_of_vault_offers() {
+    let vault_offer = offer(OfferMode::Vault, Pubkey::new_unique());
+    let offer_key = offer_address(&vault_offer.maker, vault_offer.id);
+
+    let cancel = cancel_offer_ix(&vault_offer, &spl_token::ID);
+
+    assert!(has_account(&cancel, &vault_address(&offer_key)));
+    assert!(cancel.accounts[0].is_signer);
+}
+
+#[test]
+fn market_address_does_not_depend_on_the_mint_order() {
+    let mint_a = Pubkey::new_unique();
+    let mint_b = Pubkey::new_unique();
+
+    assert_eq!(market_address(&mint_a, &mint_b), market_address(&mint_b, &mint_a));
+}
+
+#[test]
+fn accounts_deserialize_from_their_data() {
+    let offer = offer(OfferMode::Vault, Pubkey::new_unique());
+    let maker_state = MakerState {
+        maker: offer.maker,
+        offer_nonce: 8,
+        bump: 254,
+    };
+
+    let deserialized = deserialize_offer(&serialize(&offer)).unwrap();
+    assert_eq!(deserialized.id, offer.id);
+    assert_eq!(deserialized.maker, offer.maker);
+    assert_eq!(deserialized.mode, offer.mode);
+    assert_eq!(next_offer_id(None).unwrap(), 0);
+    assert_eq!(next_offer_id(Some(&serialize(&maker_state))).unwrap(), 8);
+    assert!(deserialize_offer(&serialize(&maker_state)).is_err());
+}
@@ -3,3 +3,11 @@ use anchor_lang::prelude::*;
 #[constant]
 pub const SEED: &str = "anchor";
 pub const ANCHOR_DISCRIMINATOR: usize = 8;
+
+pub const BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+#[constant]
+pub const MAX_FEE_BASIS_POINTS: u16 = 1_000;
+
+pub const MAX_BASKET_LEGS: usize = 5; // per side of a basket offer
+
+pub const MAX_ORDERS_PER_SIDE: usize = 32; // bids or asks kept by a market
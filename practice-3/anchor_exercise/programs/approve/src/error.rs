@@ -1,7 +1,83 @@
 use anchor_lang::prelude::*;
 
+/// Clients map errors by code, 6000 plus the variant index, so new variants
+/// are appended at the end
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Custom error message")]
-    CustomError,
+    #[msg("Offered and wanted amounts must be greater than zero")]
+    ZeroAmount,
+    #[msg("Offered and wanted tokens must have different mints")]
+    IdenticalMints,
+    #[msg("Offer expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Only the offer maker can perform this action")]
+    NotOfferMaker,
+    #[msg("Token mint does not match the offer")]
+    OfferMintMismatch,
+    #[msg("The maker can not take their own offer")]
+    SelfTake,
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Offer has not expired yet")]
+    OfferNotExpired,
+    #[msg("The maker has revoked the delegation to the offer")]
+    DelegateRevoked,
+    #[msg("Delegated allowance is less than the offered amount")]
+    InsufficientDelegatedAmount,
+    #[msg("Fill amount must be greater than zero and not exceed the remaining amount")]
+    InvalidFillAmount,
+    #[msg("Fill amount is too small to receive any offered tokens")]
+    FillTooSmall,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Maker token balance is less than the offered amount")]
+    InsufficientMakerBalance,
+    #[msg("Vault account is required for vault offers")]
+    MissingVault,
+    #[msg("Vault account must only be passed for vault offers")]
+    UnexpectedVault,
+    #[msg("Only the config admin can perform this action")]
+    NotAdmin,
+    #[msg("Fee basis points exceed the allowed maximum")]
+    InvalidFeeBasisPoints,
+    #[msg("Treasury does not match the config")]
+    TreasuryMismatch,
+    #[msg("The program is paused")]
+    ProgramPaused,
+    #[msg("The offer is reserved for another taker")]
+    TakerNotAllowed,
+    #[msg("Taker is not on the offer allowlist")]
+    NotOnAllowlist,
+    #[msg("Received or paid amounts are worse than the taker expected")]
+    SlippageExceeded,
+    #[msg("Non-transferable mints can not be traded")]
+    NonTransferableMint,
+    #[msg("Mints with a permanent delegate are not supported")]
+    PermanentDelegateMint,
+    #[msg("Transfer hook program account is missing from the remaining accounts")]
+    MissingTransferHookAccounts,
+    #[msg("Mints whose token accounts start frozen are not supported")]
+    DefaultFrozenMint,
+    #[msg("Token B accounts are required for token offers")]
+    MissingTokenBAccounts,
+    #[msg("Token B accounts must not be passed for SOL offers")]
+    UnexpectedTokenBAccounts,
+    #[msg("Basket offers need one to five legs on each side with distinct mints")]
+    InvalidBasketLegs,
+    #[msg("Remaining accounts do not match the basket offer legs")]
+    BasketAccountMismatch,
+    #[msg("Remaining accounts do not match the listed offers")]
+    OfferAccountMismatch,
+    #[msg("Market mints must be passed in canonical order")]
+    NonCanonicalMarket,
+    #[msg("Market does not match the offer")]
+    MarketMismatch,
+    #[msg("Market account is required for offers listed on a market")]
+    MissingMarket,
+    #[msg("Market order book side is full of orders priced at least as well")]
+    MarketFull,
+    #[msg("Only the program upgrade authority can perform this action")]
+    NotUpgradeAuthority,
+    #[msg("Only vault offers can be listed on a market")]
+    MarketRequiresVault,
 }
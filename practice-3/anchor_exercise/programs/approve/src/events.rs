@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+
+use crate::BasketLeg;
+
+#[event]
+pub struct PauseChanged {
+    pub admin: Pubkey,
+    pub paused: bool,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OfferMade {
+    pub offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_wanted_amount: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OfferTaken {
+    pub offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_a_amount: u64, // token A sent to the taker
+    pub token_b_amount: u64, // token B paid by the taker, including the fee
+    pub fee: u64,
+    pub remaining_a: u64,
+    pub remaining_b: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OfferUpdated {
+    pub offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_wanted_amount: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OfferCancelled {
+    pub offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub remaining_a: u64,
+    pub remaining_b: u64,
+    pub expired: bool, // closed by `close_expired_offer` rather than by the maker
+    pub slot: u64,
+}
+
+#[event]
+pub struct BasketOfferMade {
+    pub basket_offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub offered: Vec<BasketLeg>,
+    pub wanted: Vec<BasketLeg>,
+    pub slot: u64,
+}
+
+#[event]
+pub struct BasketOfferTaken {
+    pub basket_offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub fees: Vec<u64>, // protocol fee of each wanted leg
+    pub slot: u64,
+}
+
+#[event]
+pub struct BasketOfferCancelled {
+    pub basket_offer: Pubkey,
+    pub id: u64,
+    pub maker: Pubkey,
+    pub expired: bool, // closed by `close_expired_basket_offer` rather than by the maker
+    pub slot: u64,
+}
+
+#[event]
+pub struct MarketCreated {
+    pub market: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub slot: u64,
+}
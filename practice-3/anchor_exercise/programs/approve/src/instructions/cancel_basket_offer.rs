@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{revoke, Revoke};
+
+use crate::{
+    error::ErrorCode,
+    events::BasketOfferCancelled,
+    instructions::shared::{basket_leg_token_account, basket_leg_token_program},
+    BasketOffer,
+};
+
+/// Remaining accounts of every offered leg: maker token account, token program
+pub const CANCEL_OFFERED_LEG_ACCOUNTS: usize = 2;
+
+#[derive(Accounts)]
+pub struct CancelBasketOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>, // only the maker can cancel the offer and gets the rent back
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        seeds = [b"basket", maker.key().as_ref(), basket_offer.id.to_le_bytes().as_ref()],
+        bump = basket_offer.bump
+    )]
+    pub basket_offer: Account<'info, BasketOffer>,
+}
+
+/// Revokes the delegations granted to the basket offer PDA in
+/// `make_basket_offer`, the account itself is closed to the maker by the
+/// `close` constraint.
+///
+/// Remaining accounts are the accounts of every offered leg, see
+/// `CANCEL_OFFERED_LEG_ACCOUNTS`.
+pub fn revoke_basket_delegations<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CancelBasketOffer<'info>>,
+) -> Result<()> {
+    let basket_offer = &ctx.accounts.basket_offer;
+    revoke_leg_delegations(basket_offer, &ctx.accounts.maker.to_account_info(), ctx.remaining_accounts)?;
+
+    emit!(BasketOfferCancelled {
+        basket_offer: basket_offer.key(),
+        id: basket_offer.id,
+        maker: basket_offer.maker,
+        expired: false,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
+
+/// Revokes the delegation of every offered leg that still points to the
+/// basket offer PDA, signed by the maker
+///
+/// `leg_accounts` are the accounts of every offered leg, see
+/// `CANCEL_OFFERED_LEG_ACCOUNTS`. A delegation the maker has since granted
+/// to someone else is left untouched.
+///
+/// # Errors
+///
+/// Fails with `BasketAccountMismatch` if the accounts do not match the
+/// offered legs.
+pub fn revoke_leg_delegations<'info>(
+    basket_offer: &Account<'info, BasketOffer>,
+    maker: &AccountInfo<'info>,
+    leg_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    require!(
+        leg_accounts.len() == basket_offer.offered.len() * CANCEL_OFFERED_LEG_ACCOUNTS,
+        ErrorCode::BasketAccountMismatch
+    );
+
+    for (leg, accounts) in basket_offer
+        .offered
+        .iter()
+        .zip(leg_accounts.chunks_exact(CANCEL_OFFERED_LEG_ACCOUNTS))
+    {
+        let [maker_token_account, token_program] = accounts else {
+            return err!(ErrorCode::BasketAccountMismatch);
+        };
+        let maker_token_account = basket_leg_token_account(maker_token_account, &leg.mint, maker.key)?;
+        let token_program =
+            basket_leg_token_program(token_program, &maker_token_account.to_account_info())?;
+        if maker_token_account.delegate != Some(basket_offer.key()).into() {
+            continue;
+        }
+
+        let revoke_accounts = Revoke {
+            source: maker_token_account.to_account_info(),
+            authority: maker.clone(),
+        };
+        let cpi_ctx = CpiContext::new(token_program.to_account_info(), revoke_accounts);
+        revoke(cpi_ctx)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{revoke, Mint, Revoke, TokenAccount, TokenInterface};
+
+use crate::{
+    error::ErrorCode,
+    events::OfferCancelled,
+    instructions::shared::{close_vault, listed_market},
+    Market, Offer, OfferMode,
+};
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>, // only the maker can cancel the offer and gets the rent back
+
+    #[account(mut, mint::token_program = token_program)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>, // receives transfer fees withheld in a closed vault
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>, // account whose delegation to the offer is revoked
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        has_one = token_mint_a @ ErrorCode::OfferMintMismatch,
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", offer.key().as_ref()],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = offer,
+        token::token_program = token_program,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // only for OfferMode::Vault offers
+
+    #[account(mut)]
+    pub market: Option<Box<Account<'info, Market>>>, // only for offers listed on a market
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Gives the maker back control over the offered tokens, either by revoking
+/// the delegation or by emptying and closing the vault, and removes the
+/// offer from its market.
+pub fn release_offered_tokens<'info>(mut ctx: Context<'_, '_, '_, 'info, CancelOffer<'info>>) -> Result<()> {
+    match ctx.accounts.offer.mode {
+        OfferMode::Delegation => revoke_delegation(&ctx)?,
+        OfferMode::Vault => refund_vault(&mut ctx)?,
+    }
+    if let Some(market) = listed_market(&mut ctx.accounts.market, &ctx.accounts.offer)? {
+        market.remove(ctx.accounts.offer.key());
+    }
+
+    let offer = &ctx.accounts.offer;
+    emit!(OfferCancelled {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        token_mint_a: offer.token_mint_a,
+        token_mint_b: offer.token_mint_b,
+        remaining_a: offer.remaining_a,
+        remaining_b: offer.remaining_b,
+        expired: false,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
+
+/// Revokes the delegation granted to the offer PDA in `make_offer`.
+///
+/// The delegate is only revoked while it still points to this offer, so a
+/// delegation the maker has since granted to someone else is left untouched.
+/// The offer account itself is closed to the maker by the `close` constraint.
+pub fn revoke_delegation(ctx: &Context<CancelOffer>) -> Result<()> {
+    let maker_token_account_a = &ctx.accounts.maker_token_account_a;
+    if maker_token_account_a.delegate != Some(ctx.accounts.offer.key()).into() {
+        return Ok(());
+    }
+
+    let revoke_accounts = Revoke {
+        source: maker_token_account_a.to_account_info(),
+        authority: ctx.accounts.maker.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), revoke_accounts);
+
+    revoke(cpi_ctx)
+}
+
+/// Returns the tokens held in the vault to the maker and closes the vault,
+/// transfer hook accounts for token A are passed as remaining accounts
+pub fn refund_vault<'info>(ctx: &mut Context<'_, '_, '_, 'info, CancelOffer<'info>>) -> Result<()> {
+    let remaining_accounts = ctx.remaining_accounts;
+    let accounts = &mut ctx.accounts;
+    let vault = accounts.vault.as_deref_mut().ok_or(ErrorCode::MissingVault)?;
+    close_vault(
+        &accounts.offer,
+        vault,
+        &accounts.maker_token_account_a,
+        accounts.maker.to_account_info(),
+        &accounts.token_mint_a,
+        &accounts.token_program,
+        remaining_accounts,
+    )
+}
@@ -0,0 +1,65 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::ErrorCode,
+    events::BasketOfferCancelled,
+    instructions::cancel_basket_offer::revoke_leg_delegations,
+    BasketOffer,
+};
+
+#[derive(Accounts)]
+pub struct CloseExpiredBasketOffer<'info> {
+    pub closer: Signer<'info>, // anyone can close an expired basket offer
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>, // receives the rent of the closed basket offer
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        seeds = [b"basket", maker.key().as_ref(), basket_offer.id.to_le_bytes().as_ref()],
+        bump = basket_offer.bump
+    )]
+    pub basket_offer: Account<'info, BasketOffer>,
+}
+
+/// Closes an expired basket offer, its rent goes back to the maker through
+/// the `close` constraint
+///
+/// Remaining accounts are optional: the accounts of every offered leg, see
+/// `CANCEL_OFFERED_LEG_ACCOUNTS`, revoke the delegations that still point to
+/// the basket offer PDA. Only the maker can revoke them, so anyone else
+/// leaves them in place. They are harmless once the basket offer is closed,
+/// because offer ids are never reused and the program only signs for a
+/// basket offer PDA that holds an open offer.
+///
+/// # Errors
+///
+/// Fails with `OfferNotExpired` before the expiry, with `NotOfferMaker` if
+/// leg accounts are passed without the maker's signature and with
+/// `BasketAccountMismatch` if they do not match the offered legs.
+pub fn close_expired_basket<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseExpiredBasketOffer<'info>>,
+) -> Result<()> {
+    let basket_offer = &ctx.accounts.basket_offer;
+    require!(
+        Clock::get()?.unix_timestamp >= basket_offer.expires_at,
+        ErrorCode::OfferNotExpired
+    );
+
+    if !ctx.remaining_accounts.is_empty() {
+        let maker = ctx.accounts.maker.to_account_info();
+        require!(maker.is_signer, ErrorCode::NotOfferMaker);
+        revoke_leg_delegations(basket_offer, &maker, ctx.remaining_accounts)?;
+    }
+
+    emit!(BasketOfferCancelled {
+        basket_offer: basket_offer.key(),
+        id: basket_offer.id,
+        maker: basket_offer.maker,
+        expired: true,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
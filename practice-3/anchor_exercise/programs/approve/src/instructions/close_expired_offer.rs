@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::ErrorCode,
+    events::OfferCancelled,
+    instructions::shared::{close_vault, listed_market},
+    Market, Offer, OfferMode,
+};
+
+#[derive(Accounts)]
+pub struct CloseExpiredOffer<'info> {
+    pub closer: Signer<'info>, // anyone can close an expired offer
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>, // receives the rent of the closed offer
+
+    #[account(mut, mint::token_program = token_program)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>, // receives transfer fees withheld in a closed vault
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>, // receives the vault refund
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        has_one = token_mint_a @ ErrorCode::OfferMintMismatch,
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", offer.key().as_ref()],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = offer,
+        token::token_program = token_program,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // only for OfferMode::Vault offers
+
+    #[account(mut)]
+    pub market: Option<Box<Account<'info, Market>>>, // only for offers listed on a market
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Checks that the offer has expired so it can be closed to the maker.
+///
+/// The delegation on the maker's token account can only be revoked by the
+/// maker, so it is left in place. It is harmless once the offer is closed,
+/// because the program only signs for an offer PDA that holds an open offer.
+pub fn check_offer_expired(ctx: &Context<CloseExpiredOffer>) -> Result<()> {
+    require!(
+        Clock::get()?.unix_timestamp >= ctx.accounts.offer.expires_at,
+        ErrorCode::OfferNotExpired
+    );
+    Ok(())
+}
+
+/// Removes the expired offer from the order book of its market
+pub fn delist_expired_offer(ctx: &mut Context<CloseExpiredOffer>) -> Result<()> {
+    if let Some(market) = listed_market(&mut ctx.accounts.market, &ctx.accounts.offer)? {
+        market.remove(ctx.accounts.offer.key());
+    }
+    Ok(())
+}
+
+/// Returns the tokens of a vault offer to the maker and closes the vault,
+/// transfer hook accounts for token A are passed as remaining accounts
+pub fn refund_expired_vault<'info>(
+    ctx: &mut Context<'_, '_, '_, 'info, CloseExpiredOffer<'info>>,
+) -> Result<()> {
+    if ctx.accounts.offer.mode != OfferMode::Vault {
+        return Ok(());
+    }
+    let remaining_accounts = ctx.remaining_accounts;
+    let accounts = &mut ctx.accounts;
+    let vault = accounts.vault.as_deref_mut().ok_or(ErrorCode::MissingVault)?;
+    close_vault(
+        &accounts.offer,
+        vault,
+        &accounts.maker_token_account_a,
+        accounts.maker.to_account_info(),
+        &accounts.token_mint_a,
+        &accounts.token_program,
+        remaining_accounts,
+    )
+}
+
+/// Emits `OfferCancelled` for the expired offer
+pub fn emit_offer_closed(ctx: &Context<CloseExpiredOffer>) -> Result<()> {
+    let offer = &ctx.accounts.offer;
+    emit!(OfferCancelled {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        token_mint_a: offer.token_mint_a,
+        token_mint_b: offer.token_mint_b,
+        remaining_a: offer.remaining_a,
+        remaining_b: offer.remaining_b,
+        expired: true,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
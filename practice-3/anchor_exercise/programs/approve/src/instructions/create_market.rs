@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::Mint;
+
+use crate::{error::ErrorCode, events::MarketCreated, Market, ANCHOR_DISCRIMINATOR};
+
+#[derive(Accounts)]
+pub struct CreateMarket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>, // anyone can create the market of a pair and pays for it
+
+    pub base_mint: InterfaceAccount<'info, Mint>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = ANCHOR_DISCRIMINATOR + Market::INIT_SPACE,
+        seeds = [b"market", base_mint.key().as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub market: Box<Account<'info, Market>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates the empty order book of a pair
+///
+/// # Errors
+///
+/// Fails if the base mint is not the smaller of the two mints, so every pair
+/// has exactly one market.
+pub fn open_market(ctx: Context<CreateMarket>) -> Result<()> {
+    let base_mint = ctx.accounts.base_mint.key();
+    let quote_mint = ctx.accounts.quote_mint.key();
+    require!(base_mint < quote_mint, ErrorCode::NonCanonicalMarket);
+
+    ctx.accounts.market.set_inner(Market {
+        base_mint,
+        quote_mint,
+        bids: Vec::new(),
+        asks: Vec::new(),
+        bump: ctx.bumps.market,
+    });
+
+    emit!(MarketCreated {
+        market: ctx.accounts.market.key(),
+        base_mint,
+        quote_mint,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
@@ -1,9 +1,56 @@
 use anchor_lang::prelude::*;
 
+use crate::{error::ErrorCode, program::Approve, Config, ANCHOR_DISCRIMINATOR, MAX_FEE_BASIS_POINTS, SEED};
+
 #[derive(Accounts)]
-pub struct Initialize {}
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>, // the upgrade authority, becomes the program admin and pays for the global state
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Approve>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ ErrorCode::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = ANCHOR_DISCRIMINATOR + Config::INIT_SPACE,
+        seeds = [SEED.as_bytes()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates the global program state with the signer as admin
+///
+/// Only the upgrade authority of the program can initialize it, so nobody
+/// can claim the admin, fee and treasury of a fresh deployment first.
+///
+/// # Errors
+///
+/// Fails if the signer is not the upgrade authority, the fee is above
+/// `MAX_FEE_BASIS_POINTS` or the program has already been initialized.
+pub fn handler(ctx: Context<Initialize>, fee_basis_points: u16, treasury: Pubkey) -> Result<()> {
+    require!(
+        fee_basis_points <= MAX_FEE_BASIS_POINTS,
+        ErrorCode::InvalidFeeBasisPoints
+    );
 
-pub fn handler(ctx: Context<Initialize>) -> Result<()> {
-    msg!("Greetings from: {:?}", ctx.program_id);
+    ctx.accounts.config.set_inner(Config {
+        admin: ctx.accounts.admin.key(),
+        fee_basis_points,
+        treasury,
+        paused: false,
+        offer_count: 0,
+        bump: ctx.bumps.config,
+    });
+    msg!("Initialized program: {:?}", ctx.program_id);
     Ok(())
 }
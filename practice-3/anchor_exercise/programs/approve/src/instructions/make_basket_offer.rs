@@ -0,0 +1,178 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{approve, Approve};
+
+use crate::{
+    error::ErrorCode,
+    events::BasketOfferMade,
+    instructions::shared::{
+        basket_leg_mint, basket_leg_token_account, basket_leg_token_program,
+        consume_offer_nonce, validate_mint_extensions,
+    },
+    BasketLeg, BasketOffer, Config, MakerState, ANCHOR_DISCRIMINATOR, MAX_BASKET_LEGS, SEED,
+};
+
+/// Remaining accounts of every offered leg: mint, maker token account, token program
+pub const MAKE_OFFERED_LEG_ACCOUNTS: usize = 3;
+
+/// Remaining accounts of every wanted leg: mint
+pub const MAKE_WANTED_LEG_ACCOUNTS: usize = 1;
+
+#[derive(Accounts)]
+#[instruction(offered: Vec<BasketLeg>, wanted: Vec<BasketLeg>)]
+pub struct MakeBasketOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>, // delegates the offered tokens and pays for the basket offer account
+
+    #[account(mut, seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = ANCHOR_DISCRIMINATOR + MakerState::INIT_SPACE,
+        seeds = [b"maker", maker.key().as_ref()],
+        bump
+    )]
+    pub maker_state: Box<Account<'info, MakerState>>, // its offer nonce becomes the id of the basket offer
+
+    #[account(
+        init,
+        payer = maker,
+        space = BasketOffer::space(offered.len(), wanted.len()),
+        seeds = [b"basket", maker.key().as_ref(), maker_state.offer_nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub basket_offer: Account<'info, BasketOffer>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Checks the basket terms and the mints of the wanted legs
+///
+/// Remaining accounts are the accounts of every offered leg followed by the
+/// mint of every wanted leg, see `MAKE_OFFERED_LEG_ACCOUNTS` and
+/// `MAKE_WANTED_LEG_ACCOUNTS`.
+///
+/// # Errors
+///
+/// Fails if the program is paused, either side has no legs or more than
+/// `MAX_BASKET_LEGS`, a mint appears twice, an amount is zero, the offer
+/// would already be expired, the remaining accounts do not match the legs or
+/// a wanted mint has a Token-2022 extension the escrow does not support.
+pub fn validate_basket_offer<'info>(
+    context: &Context<'_, '_, 'info, 'info, MakeBasketOffer<'info>>,
+    offered: &[BasketLeg],
+    wanted: &[BasketLeg],
+    expires_at: i64,
+) -> Result<()> {
+    require!(!context.accounts.config.paused, ErrorCode::ProgramPaused);
+    require!(
+        (1..=MAX_BASKET_LEGS).contains(&offered.len())
+            && (1..=MAX_BASKET_LEGS).contains(&wanted.len()),
+        ErrorCode::InvalidBasketLegs
+    );
+
+    let legs: Vec<&BasketLeg> = offered.iter().chain(wanted).collect();
+    for (index, leg) in legs.iter().enumerate() {
+        require!(leg.amount > 0, ErrorCode::ZeroAmount);
+        require!(
+            legs[index + 1..].iter().all(|other| other.mint != leg.mint),
+            ErrorCode::InvalidBasketLegs
+        );
+    }
+    require!(
+        expires_at > Clock::get()?.unix_timestamp,
+        ErrorCode::InvalidExpiry
+    );
+
+    let offered_accounts_len = offered.len() * MAKE_OFFERED_LEG_ACCOUNTS;
+    require!(
+        context.remaining_accounts.len()
+            == offered_accounts_len + wanted.len() * MAKE_WANTED_LEG_ACCOUNTS,
+        ErrorCode::BasketAccountMismatch
+    );
+    for (leg, mint) in wanted
+        .iter()
+        .zip(&context.remaining_accounts[offered_accounts_len..])
+    {
+        validate_mint_extensions(&basket_leg_mint(mint, leg)?)?;
+    }
+    Ok(())
+}
+
+/// Approves the basket offer PDA as the delegate of every offered leg
+///
+/// # Errors
+///
+/// Fails if the accounts of a leg do not match it, the maker token account
+/// is not owned by the maker or an offered mint has a Token-2022 extension
+/// the escrow does not support.
+pub fn delegate_basket_legs<'info>(
+    context: &Context<'_, '_, 'info, 'info, MakeBasketOffer<'info>>,
+    offered: &[BasketLeg],
+) -> Result<()> {
+    let maker = context.accounts.maker.key();
+    let leg_accounts = context
+        .remaining_accounts
+        .chunks_exact(MAKE_OFFERED_LEG_ACCOUNTS);
+
+    for (leg, accounts) in offered.iter().zip(leg_accounts) {
+        let [mint, maker_token_account, token_program] = accounts else {
+            return err!(ErrorCode::BasketAccountMismatch);
+        };
+        let mint = basket_leg_mint(mint, leg)?;
+        validate_mint_extensions(&mint)?;
+        let maker_token_account = basket_leg_token_account(maker_token_account, &leg.mint, &maker)?;
+        let token_program = basket_leg_token_program(token_program, &mint.to_account_info())?;
+
+        let delegate_accounts = Approve {
+            to: maker_token_account.to_account_info(),
+            delegate: context.accounts.basket_offer.to_account_info(),
+            authority: context.accounts.maker.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(token_program.to_account_info(), delegate_accounts);
+        approve(cpi_ctx, leg.amount)?;
+    }
+    Ok(())
+}
+
+/// Saves the basket offer in its PDA account, its id is the maker's offer nonce
+pub fn save_basket_offer(
+    context: Context<MakeBasketOffer>,
+    offered: Vec<BasketLeg>,
+    wanted: Vec<BasketLeg>,
+    expires_at: i64,
+) -> Result<()> {
+    let id = consume_offer_nonce(
+        &mut context.accounts.maker_state,
+        context.accounts.maker.key(),
+        context.bumps.maker_state,
+    )?;
+
+    context.accounts.basket_offer.set_inner(BasketOffer {
+        id,
+        maker: context.accounts.maker.key(),
+        offered,
+        wanted,
+        expires_at,
+        bump: context.bumps.basket_offer,
+    });
+
+    let config = &mut context.accounts.config;
+    config.offer_count = config
+        .offer_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let basket_offer = &context.accounts.basket_offer;
+    emit!(BasketOfferMade {
+        basket_offer: basket_offer.key(),
+        id: basket_offer.id,
+        maker: basket_offer.maker,
+        offered: basket_offer.offered.clone(),
+        wanted: basket_offer.wanted.clone(),
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
@@ -8,45 +8,131 @@ use anchor_spl::{
     token_interface::{Mint, TokenAccount, TokenInterface, Approve, approve},
 };
 
-use crate::{Offer, ANCHOR_DISCRIMINATOR};
+use crate::{
+    error::ErrorCode,
+    events::OfferMade,
+    instructions::shared::{
+        amount_with_transfer_fee, consume_offer_nonce, required_vault, transfer_checked_with_hook,
+        validate_mint_extensions, validate_transfer_hook_accounts,
+    },
+    Config, MakerState, Market, Offer, OfferMode, ANCHOR_DISCRIMINATOR, SEED,
+};
 
 // список аккаунтів які передаються в інструкцію delegate_offered_tokens_to_vault
 // яка делегує токени в vault
 #[derive(Accounts)]
-#[instruction(id: u64)]
 pub struct MakeOffer<'info> {
     #[account(mut)]
     pub maker: Signer<'info>, // хто буде делегувати свої токени в vault та платити комісійні
 
-    #[account(mint::token_program = token_program)]
+    #[account(mint::token_program = token_program_a)]
     pub token_mint_a: InterfaceAccount<'info, Mint>, // токен який передаємо в vault
 
-    #[account(mint::token_program = token_program)]
+    #[account(mint::token_program = token_program_b)]
     pub token_mint_b: InterfaceAccount<'info, Mint>, // токен який очікуємо отримати замість токена А
 
     #[account(
         mut,
         associated_token::mint = token_mint_a,
         associated_token::authority = maker,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
     pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>, // аккаунт токена А з якого в результаті всієї операції будуть списані токени А 
+
+    #[account(mut, seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>, // global program state, counts the offers made
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = ANCHOR_DISCRIMINATOR + MakerState::INIT_SPACE,
+        seeds = [b"maker", maker.key().as_ref()],
+        bump
+    )]
+    pub maker_state: Box<Account<'info, MakerState>>, // its offer nonce becomes the id of the new offer
     
     #[account(
         init,
         payer = maker,
         space = ANCHOR_DISCRIMINATOR + Offer::INIT_SPACE,
-        seeds = [b"offer", maker.key().as_ref(), id.to_le_bytes().as_ref()],
+        seeds = [b"offer", maker.key().as_ref(), maker_state.offer_nonce.to_le_bytes().as_ref()],
         bump
     )]
     pub offer: Account<'info, Offer>, // аккаунт, який містить інформацію про операцію, буде створений під час виконання інструкції 
 
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"vault", offer.key().as_ref()],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = offer,
+        token::token_program = token_program_a
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // PDA token account holding token A, only passed for OfferMode::Vault offers
+
+    #[account(
+        mut,
+        seeds = [b"market", market.base_mint.as_ref(), market.quote_mint.as_ref()],
+        bump = market.bump
+    )]
+    pub market: Option<Box<Account<'info, Market>>>, // optional market of the pair whose order book lists the offer
+
     pub associated_token_program: Program<'info, AssociatedToken>, 
-    pub token_program: Interface<'info, TokenInterface>,
+    pub token_program_a: Interface<'info, TokenInterface>, // token program of token A
+    pub token_program_b: Interface<'info, TokenInterface>, // token program of token B, may differ from that of token A
     pub system_program: Program<'info, System>,
 }
 
 
+/// Checks the offer terms before anything is delegated
+///
+/// # Errors
+///
+/// Fails if the program is paused, either amount is zero, both mints are the same, the offer
+/// would already be expired according to the `Clock` sysvar, the vault
+/// account does not match the offer mode, either mint has a Token-2022
+/// extension the escrow does not support or a delegation offer is listed on a
+/// market.
+///
+/// Transfer fees are allowed: the taker's slippage limits cover the fee on
+/// token A, and the maker receives token B minus its transfer fee.
+pub fn validate_offer(
+    context: &Context<MakeOffer>,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+    expires_at: i64,
+    mode: OfferMode,
+) -> Result<()> {
+    require!(!context.accounts.config.paused, ErrorCode::ProgramPaused);
+    require!(
+        token_a_offered_amount > 0 && token_b_wanted_amount > 0,
+        ErrorCode::ZeroAmount
+    );
+    require_keys_neq!(
+        context.accounts.token_mint_a.key(),
+        context.accounts.token_mint_b.key(),
+        ErrorCode::IdenticalMints
+    );
+    validate_mint_extensions(&context.accounts.token_mint_a)?;
+    validate_mint_extensions(&context.accounts.token_mint_b)?;
+    require!(
+        expires_at > Clock::get()?.unix_timestamp,
+        ErrorCode::InvalidExpiry
+    );
+    match mode {
+        OfferMode::Delegation => require!(context.accounts.vault.is_none(), ErrorCode::UnexpectedVault),
+        OfferMode::Vault => require!(context.accounts.vault.is_some(), ErrorCode::MissingVault),
+    }
+    // A token account has a single delegate, so delegation offers can not
+    // back the book: every new approve silently unfunds the previous offers
+    require!(
+        mode == OfferMode::Vault || context.accounts.market.is_none(),
+        ErrorCode::MarketRequiresVault
+    );
+    Ok(())
+}
+
 pub fn delegate_offered_tokens_to_vault(
     context: &Context<MakeOffer>,
     token_a_offered_amount: u64,
@@ -60,7 +146,7 @@ pub fn delegate_offered_tokens_to_vault(
     };
 
     let cpi_context = CpiContext::new(
-        context.accounts.token_program.to_account_info(),
+        context.accounts.token_program_a.to_account_info(),
         delegate_accounts
     );
 
@@ -73,13 +159,54 @@ pub fn delegate_offered_tokens_to_vault(
     //Ok(())
 }
 
+/// Moves the offered tokens from the maker into the offer vault
+///
+/// The maker also pays the transfer fee of token A, if any, so the vault
+/// receives exactly `token_a_offered_amount`. Transfer hook accounts for
+/// token A are passed as remaining accounts.
+pub fn deposit_offered_tokens_to_vault<'info>(
+    context: &Context<'_, '_, '_, 'info, MakeOffer<'info>>,
+    token_a_offered_amount: u64,
+) -> Result<()> {
+    let vault = required_vault(&context.accounts.vault)?;
+    validate_transfer_hook_accounts(&context.accounts.token_mint_a, context.remaining_accounts)?;
+    let deposit_amount = amount_with_transfer_fee(&context.accounts.token_mint_a, token_a_offered_amount)?;
 
-/// Saves an offer in PDA account
+    transfer_checked_with_hook(
+        &context.accounts.token_program_a,
+        context.accounts.maker_token_account_a.to_account_info(),
+        &context.accounts.token_mint_a,
+        vault.to_account_info(),
+        context.accounts.maker.to_account_info(),
+        context.remaining_accounts,
+        deposit_amount,
+        &[],
+    )
+}
+
+/// Saves an offer in PDA account and lists it on the market, if one was passed
+///
+/// The offer id is the maker's offer nonce, which is advanced for the next offer.
 ///
 /// # Errors
 ///
-/// This function will return an error if .
-pub fn save_offer(context: Context<MakeOffer>, id: u64, token_a_amount: u64, token_b_wanted_amount: u64) -> Result<()> {
+/// Fails if the market is for another pair or its side of the book is full
+/// of orders priced at least as well.
+pub fn save_offer(
+    context: Context<MakeOffer>,
+    token_a_amount: u64,
+    token_b_wanted_amount: u64,
+    expires_at: i64,
+    mode: OfferMode,
+    allowed_taker: Option<Pubkey>,
+    allowlist_root: Option<[u8; 32]>,
+) -> Result<()> {
+    let id = consume_offer_nonce(
+        &mut context.accounts.maker_state,
+        context.accounts.maker.key(),
+        context.bumps.maker_state,
+    )?;
+
     context.accounts.offer.set_inner(Offer {
         id,
         maker: context.accounts.maker.key(),
@@ -87,7 +214,38 @@ pub fn save_offer(context: Context<MakeOffer>, id: u64, token_a_amount: u64, tok
         token_mint_b: context.accounts.token_mint_b.key(),
         token_a_amount,
         token_b_wanted_amount,
+        remaining_a: token_a_amount,
+        remaining_b: token_b_wanted_amount,
+        expires_at,
+        mode,
+        allowed_taker,
+        allowlist_root,
+        market: context.accounts.market.as_ref().map(|market| market.key()),
         bump: context.bumps.offer,
     });
+
+    if let Some(market) = context.accounts.market.as_deref_mut() {
+        let offer = &context.accounts.offer;
+        market.validate_pair(offer)?;
+        market.list(offer.key(), offer)?;
+    }
+
+    let config = &mut context.accounts.config;
+    config.offer_count = config
+        .offer_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let offer = &context.accounts.offer;
+    emit!(OfferMade {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        token_mint_a: offer.token_mint_a,
+        token_mint_b: offer.token_mint_b,
+        token_a_amount: offer.token_a_amount,
+        token_b_wanted_amount: offer.token_b_wanted_amount,
+        slot: Clock::get()?.slot,
+    });
     Ok(())
 }
@@ -1,5 +1,43 @@
+pub mod shared;
+
+pub mod initialize;
+pub use initialize::*;
+
 pub mod make_offer;
 pub use make_offer::*;
 
 pub mod take_offer;
-pub use take_offer::*;
\ No newline at end of file
+pub use take_offer::*;
+
+pub mod cancel_offer;
+pub use cancel_offer::*;
+
+pub mod close_expired_offer;
+pub use close_expired_offer::*;
+
+pub mod update_config;
+pub use update_config::*;
+
+pub mod set_paused;
+pub use set_paused::*;
+
+pub mod update_offer;
+pub use update_offer::*;
+
+pub mod make_basket_offer;
+pub use make_basket_offer::*;
+
+pub mod take_basket_offer;
+pub use take_basket_offer::*;
+
+pub mod cancel_basket_offer;
+pub use cancel_basket_offer::*;
+
+pub mod close_expired_basket_offer;
+pub use close_expired_basket_offer::*;
+
+pub mod take_offers;
+pub use take_offers::*;
+
+pub mod create_market;
+pub use create_market::*;
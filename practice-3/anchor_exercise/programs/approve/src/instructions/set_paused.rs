@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::ErrorCode, events::PauseChanged, Config, SEED};
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::NotAdmin,
+        seeds = [SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Pauses or resumes making and taking offers.
+///
+/// Cancelling and closing offers stays possible while paused, so makers can
+/// always get their tokens and rent back.
+pub fn apply_pause(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    if config.paused == paused {
+        return Ok(());
+    }
+
+    config.paused = paused;
+    emit!(PauseChanged {
+        admin: ctx.accounts.admin.key(),
+        paused,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
@@ -0,0 +1,603 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+
+use anchor_spl::{
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        extension::{
+            default_account_state::DefaultAccountState, non_transferable::NonTransferable,
+            permanent_delegate::get_permanent_delegate,
+            transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+            transfer_hook, BaseStateWithExtensions, StateWithExtensions,
+        },
+        native_mint,
+        onchain::invoke_transfer_checked,
+        state::{Account as TokenAccountState, AccountState, Mint as MintState},
+    },
+    token_2022_extensions::transfer_fee::{harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint},
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{error::ErrorCode, events::OfferTaken, BasketLeg, MakerState, Market, Offer, OfferMode};
+
+/// Returns the maker's current offer nonce as the id of a new offer and
+/// advances it, so ids are never reused
+pub fn consume_offer_nonce(maker_state: &mut MakerState, maker: Pubkey, bump: u8) -> Result<u64> {
+    let id = maker_state.offer_nonce;
+    maker_state.maker = maker;
+    maker_state.offer_nonce = id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+    maker_state.bump = bump;
+    Ok(id)
+}
+
+/// Returns the market the offer is listed on
+///
+/// # Errors
+///
+/// Fails with `MissingMarket` if the offer is listed but its market was not
+/// passed, and with `MarketMismatch` if another market was passed.
+pub fn listed_market<'a, 'info>(
+    market: &'a mut Option<Box<Account<'info, Market>>>,
+    offer: &Offer,
+) -> Result<Option<&'a mut Account<'info, Market>>> {
+    let Some(listed_on) = offer.market else {
+        return Ok(None);
+    };
+    let market = market.as_deref_mut().ok_or(ErrorCode::MissingMarket)?;
+    require_keys_eq!(market.key(), listed_on, ErrorCode::MarketMismatch);
+    Ok(Some(market))
+}
+
+/// Returns the vault account of a vault offer
+///
+/// # Errors
+///
+/// Fails with `MissingVault` if the vault account was not passed.
+pub fn required_vault<'a, 'info>(
+    vault: &'a Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+) -> Result<&'a InterfaceAccount<'info, TokenAccount>> {
+    vault.as_deref().ok_or_else(|| ErrorCode::MissingVault.into())
+}
+
+/// Returns the address of the vault of a vault offer, a token account at a
+/// PDA of the program so nobody else can create it ahead of the offer
+pub fn vault_address(offer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", offer.as_ref()], &crate::ID).0
+}
+
+/// Returns whether `mint` is the native SOL mint of SPL Token or Token-2022.
+///
+/// Offers whose token B is a native mint are paid in lamports directly, so
+/// neither party has to wrap SOL.
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == native_mint::ID
+}
+
+/// `transfer_checked` CPI that also works for Token-2022 mints with a
+/// transfer hook.
+///
+/// The extra accounts required by the hook are looked up in
+/// `additional_accounts`, usually the instruction's remaining accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked_with_hook<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    additional_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    invoke_transfer_checked(
+        token_program.key,
+        from,
+        mint.to_account_info(),
+        to,
+        authority,
+        additional_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Checks that the transfer hook program of `mint`, if any, was passed in
+/// `additional_accounts`, so a missing hook fails with a clear error
+pub fn validate_transfer_hook_accounts(
+    mint: &InterfaceAccount<Mint>,
+    additional_accounts: &[AccountInfo],
+) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+
+    if let Some(hook_program_id) = transfer_hook::get_program_id(&mint_state) {
+        require!(
+            additional_accounts
+                .iter()
+                .any(|account| account.key() == hook_program_id),
+            ErrorCode::MissingTransferHookAccounts
+        );
+    }
+    Ok(())
+}
+
+/// Transfers `amount` of token A out of the offer vault, signed by the offer PDA
+pub fn transfer_from_vault<'info>(
+    offer: &Account<'info, Offer>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    additional_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let id_bytes = offer.id.to_le_bytes();
+    let bump = [offer.bump];
+    let signer_seeds: [&[&[u8]]; 1] = [&[b"offer", offer.maker.as_ref(), &id_bytes, &bump]];
+
+    transfer_checked_with_hook(
+        token_program,
+        vault.to_account_info(),
+        mint,
+        to.to_account_info(),
+        offer.to_account_info(),
+        additional_accounts,
+        amount,
+        &signer_seeds,
+    )
+}
+
+/// Sends whatever is left in the vault back to the maker and closes the
+/// vault, returning its rent to the maker.
+///
+/// Transfer fees withheld in the vault by deposits of a Token-2022 mint are
+/// harvested to the mint first, as the token program refuses to close an
+/// account that still withholds fees.
+pub fn close_vault<'info>(
+    offer: &Account<'info, Offer>,
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    maker_token_account: &InterfaceAccount<'info, TokenAccount>,
+    maker: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    additional_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    vault.reload()?;
+    if vault.amount > 0 {
+        transfer_from_vault(
+            offer,
+            vault,
+            maker_token_account,
+            mint,
+            token_program,
+            additional_accounts,
+            vault.amount,
+        )?;
+    }
+    harvest_withheld_fees(vault, mint, token_program)?;
+
+    let id_bytes = offer.id.to_le_bytes();
+    let bump = [offer.bump];
+    let signer_seeds: [&[&[u8]]; 1] = [&[b"offer", offer.maker.as_ref(), &id_bytes, &bump]];
+
+    let close_accounts = CloseAccount {
+        account: vault.to_account_info(),
+        destination: maker,
+        authority: offer.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        close_accounts,
+        &signer_seeds,
+    );
+
+    close_account(cpi_ctx)
+}
+
+/// Moves the transfer fees withheld in a Token-2022 token account to its
+/// mint, does nothing for accounts that withhold no fees
+fn harvest_withheld_fees<'info>(
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let account_info = token_account.to_account_info();
+    let withheld_amount = {
+        let account_data = account_info.try_borrow_data()?;
+        let account_state = StateWithExtensions::<TokenAccountState>::unpack(&account_data)?;
+        account_state
+            .get_extension::<TransferFeeAmount>()
+            .map_or(0, |transfer_fee_amount| u64::from(transfer_fee_amount.withheld_amount))
+    };
+    if withheld_amount == 0 {
+        return Ok(());
+    }
+
+    let harvest_accounts = HarvestWithheldTokensToMint {
+        token_program_id: token_program.to_account_info(),
+        mint: mint.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new(token_program.to_account_info(), harvest_accounts);
+
+    harvest_withheld_tokens_to_mint(cpi_ctx, vec![account_info])
+}
+
+/// Checks that the token B accounts are passed only for token offers
+///
+/// # Errors
+///
+/// Fails if any token B account is missing for a token offer, or passed for
+/// an offer paid in SOL.
+pub fn validate_token_b_accounts(token_mint_b: &Pubkey, passed: &[bool]) -> Result<()> {
+    if is_native_mint(token_mint_b) {
+        require!(!passed.contains(&true), ErrorCode::UnexpectedTokenBAccounts);
+    } else {
+        require!(!passed.contains(&false), ErrorCode::MissingTokenBAccounts);
+    }
+    Ok(())
+}
+
+/// Checks that `offer` can pull `token_a_amount` from the maker token account.
+///
+/// The maker keeps custody of token A and may revoke the delegation or spend
+/// the tokens after `make_offer`, so this runs before any transfer and fails
+/// instead of letting the taker send token B for nothing.
+pub fn check_maker_allowance(
+    offer: &Pubkey,
+    maker_token_account_a: &TokenAccount,
+    token_a_amount: u64,
+) -> Result<()> {
+    require!(
+        maker_token_account_a.delegate == Some(*offer).into(),
+        ErrorCode::DelegateRevoked
+    );
+    require!(
+        maker_token_account_a.delegated_amount >= token_a_amount,
+        ErrorCode::InsufficientDelegatedAmount
+    );
+    require!(
+        maker_token_account_a.amount >= token_a_amount,
+        ErrorCode::InsufficientMakerBalance
+    );
+    Ok(())
+}
+
+/// Returns the taker's token B balance, in lamports for SOL offers whose
+/// token B account is not passed
+pub fn taker_balance_b(
+    taker: &AccountInfo,
+    taker_token_account_b: Option<&InterfaceAccount<TokenAccount>>,
+) -> u64 {
+    taker_token_account_b.map_or_else(|| taker.lamports(), |account| account.amount)
+}
+
+/// Checks the taker's actual balance changes against the slippage limits,
+/// given the balances from before the take
+pub fn check_taker_balance_changes(
+    taker: &AccountInfo,
+    taker_token_account_a: &mut InterfaceAccount<TokenAccount>,
+    taker_token_account_b: Option<&mut InterfaceAccount<TokenAccount>>,
+    taker_balance_a: u64,
+    taker_balance_b: u64,
+    expected_token_a_amount: u64,
+    max_token_b_amount: u64,
+) -> Result<()> {
+    taker_token_account_a.reload()?;
+    let taker_token_account_b = match taker_token_account_b {
+        Some(taker_token_account_b) => {
+            taker_token_account_b.reload()?;
+            Some(&*taker_token_account_b)
+        }
+        None => None,
+    };
+
+    let received_a = taker_token_account_a
+        .amount
+        .checked_sub(taker_balance_a)
+        .ok_or(ErrorCode::SlippageExceeded)?;
+    let paid_b = taker_balance_b
+        .checked_sub(self::taker_balance_b(taker, taker_token_account_b))
+        .ok_or(ErrorCode::SlippageExceeded)?;
+
+    require!(
+        received_a >= expected_token_a_amount && paid_b <= max_token_b_amount,
+        ErrorCode::SlippageExceeded
+    );
+    Ok(())
+}
+
+/// Accounts that move token B in a fill
+///
+/// The token accounts are `None` for SOL offers, which are paid in lamports
+/// with system transfers between the wallets.
+pub struct TokenBAccounts<'a, 'info> {
+    pub taker: AccountInfo<'info>,
+    pub taker_token_account: Option<AccountInfo<'info>>,
+    pub maker: AccountInfo<'info>,
+    pub maker_token_account: Option<AccountInfo<'info>>,
+    pub treasury: AccountInfo<'info>,
+    pub treasury_token_account: Option<AccountInfo<'info>>,
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    pub system_program: &'a Program<'info, System>,
+}
+
+impl<'info> TokenBAccounts<'_, 'info> {
+    /// Pays for a fill: `token_b_amount` minus `fee` from the taker to the
+    /// maker and `fee` to the treasury
+    ///
+    /// # Errors
+    ///
+    /// Fails with `MissingTokenBAccounts` if a token account of a token offer
+    /// was not passed.
+    pub fn pay(&self, hook_accounts: &[AccountInfo<'info>], token_b_amount: u64, fee: u64) -> Result<()> {
+        self.send(&self.maker, &self.maker_token_account, hook_accounts, token_b_amount - fee)?;
+        if fee > 0 {
+            self.send(&self.treasury, &self.treasury_token_account, hook_accounts, fee)?;
+        }
+        Ok(())
+    }
+
+    /// Sends `amount` from the taker to the wallet for SOL offers, to its
+    /// token account otherwise
+    fn send(
+        &self,
+        wallet: &AccountInfo<'info>,
+        token_account: &Option<AccountInfo<'info>>,
+        hook_accounts: &[AccountInfo<'info>],
+        amount: u64,
+    ) -> Result<()> {
+        if is_native_mint(&self.mint.key()) {
+            let transfer_accounts = Transfer {
+                from: self.taker.clone(),
+                to: wallet.clone(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+            return transfer(cpi_ctx, amount);
+        }
+
+        let (Some(from), Some(to)) = (&self.taker_token_account, token_account) else {
+            return err!(ErrorCode::MissingTokenBAccounts);
+        };
+        transfer_checked_with_hook(
+            self.token_program,
+            from.clone(),
+            self.mint,
+            to.clone(),
+            self.taker.clone(),
+            hook_accounts,
+            amount,
+            &[],
+        )
+    }
+}
+
+/// Sends `token_a_amount` of token A to the taker, out of the vault of a
+/// vault offer or from the maker through the delegation to the offer PDA
+///
+/// Delegation offers are checked with [`check_maker_allowance`] by the
+/// callers before the taker pays.
+///
+/// # Errors
+///
+/// Fails with `MissingVault` if the vault of a vault offer was not passed.
+#[allow(clippy::too_many_arguments)]
+pub fn send_token_a<'info>(
+    offer: &Account<'info, Offer>,
+    vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    maker_token_account_a: &InterfaceAccount<'info, TokenAccount>,
+    taker_token_account_a: &InterfaceAccount<'info, TokenAccount>,
+    mint_a: &InterfaceAccount<'info, Mint>,
+    token_program_a: &Interface<'info, TokenInterface>,
+    additional_accounts: &[AccountInfo<'info>],
+    token_a_amount: u64,
+) -> Result<()> {
+    if offer.mode == OfferMode::Vault {
+        return transfer_from_vault(
+            offer,
+            vault.ok_or(ErrorCode::MissingVault)?,
+            taker_token_account_a,
+            mint_a,
+            token_program_a,
+            additional_accounts,
+            token_a_amount,
+        );
+    }
+
+    let id_bytes = offer.id.to_le_bytes();
+    let bump = [offer.bump];
+    let signer_seeds: [&[&[u8]]; 1] = [&[b"offer", offer.maker.as_ref(), &id_bytes, &bump]];
+
+    transfer_checked_with_hook(
+        token_program_a,
+        maker_token_account_a.to_account_info(),
+        mint_a,
+        taker_token_account_a.to_account_info(),
+        offer.to_account_info(),
+        additional_accounts,
+        token_a_amount,
+        &signer_seeds,
+    )
+}
+
+/// Decreases the remaining amounts of the offer by a fill, and closes the
+/// offer (and its vault) to the maker when nothing is left to fill
+///
+/// The offer keeps its remaining amounts in memory after it is closed, so
+/// the caller can still update its market entry.
+#[allow(clippy::too_many_arguments)]
+pub fn record_fill<'info>(
+    offer: &mut Account<'info, Offer>,
+    vault: Option<&mut InterfaceAccount<'info, TokenAccount>>,
+    maker_token_account_a: &InterfaceAccount<'info, TokenAccount>,
+    maker: AccountInfo<'info>,
+    mint_a: &InterfaceAccount<'info, Mint>,
+    token_program_a: &Interface<'info, TokenInterface>,
+    additional_accounts: &[AccountInfo<'info>],
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> Result<()> {
+    offer.remaining_a = offer
+        .remaining_a
+        .checked_sub(token_a_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    offer.remaining_b = offer
+        .remaining_b
+        .checked_sub(token_b_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    if offer.remaining_b > 0 {
+        return Ok(());
+    }
+
+    if let Some(vault) = vault {
+        close_vault(
+            offer,
+            vault,
+            maker_token_account_a,
+            maker.clone(),
+            mint_a,
+            token_program_a,
+            additional_accounts,
+        )?;
+    }
+    offer.close(maker)
+}
+
+/// Emits `OfferTaken` for a fill of the offer
+pub fn emit_offer_taken(
+    offer: &Account<Offer>,
+    taker: Pubkey,
+    token_a_amount: u64,
+    token_b_amount: u64,
+    fee: u64,
+) -> Result<()> {
+    emit!(OfferTaken {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        taker,
+        token_mint_a: offer.token_mint_a,
+        token_mint_b: offer.token_mint_b,
+        token_a_amount,
+        token_b_amount,
+        fee,
+        remaining_a: offer.remaining_a,
+        remaining_b: offer.remaining_b,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
+
+/// Rejects Token-2022 mints with extensions the escrow can not trade safely
+///
+/// # Errors
+///
+/// Fails for non-transferable mints, mints with a permanent delegate (who
+/// could drain a vault or the maker's delegated tokens) and mints whose new
+/// accounts start frozen. Transfer fees are accounted for by the callers and
+/// transfer hooks are run with the extra accounts the callers forward.
+pub fn validate_mint_extensions(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+
+    require!(
+        mint_state.get_extension::<NonTransferable>().is_err(),
+        ErrorCode::NonTransferableMint
+    );
+    require!(
+        get_permanent_delegate(&mint_state).is_none(),
+        ErrorCode::PermanentDelegateMint
+    );
+    if let Ok(default_account_state) = mint_state.get_extension::<DefaultAccountState>() {
+        require!(
+            default_account_state.state != AccountState::Frozen as u8,
+            ErrorCode::DefaultFrozenMint
+        );
+    }
+    Ok(())
+}
+
+/// Returns the Token-2022 transfer fee withheld from the recipient when
+/// `amount` of `mint` is sent in the current epoch, zero for mints without
+/// the transfer fee extension.
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+
+    let Ok(transfer_fee_config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    let fee = transfer_fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(fee)
+}
+
+/// Returns how much must be sent for the recipient to receive exactly
+/// `amount` of `mint` after the Token-2022 transfer fee of the current epoch.
+pub fn amount_with_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+
+    let Ok(transfer_fee_config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let fee = transfer_fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let amount = amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+    Ok(amount)
+}
+
+/// Loads the mint of a basket leg from a remaining account
+///
+/// # Errors
+///
+/// Fails with `BasketAccountMismatch` if the account is not the leg's mint.
+pub fn basket_leg_mint<'info>(
+    account: &'info AccountInfo<'info>,
+    leg: &BasketLeg,
+) -> Result<InterfaceAccount<'info, Mint>> {
+    require_keys_eq!(account.key(), leg.mint, ErrorCode::BasketAccountMismatch);
+    InterfaceAccount::try_from(account)
+}
+
+/// Loads a token account of `mint` owned by `owner` from a remaining account
+///
+/// # Errors
+///
+/// Fails with `BasketAccountMismatch` if the token account has another mint
+/// or owner.
+pub fn basket_leg_token_account<'info>(
+    account: &'info AccountInfo<'info>,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<InterfaceAccount<'info, TokenAccount>> {
+    let token_account = InterfaceAccount::<TokenAccount>::try_from(account)?;
+    require_keys_eq!(token_account.mint, *mint, ErrorCode::BasketAccountMismatch);
+    require_keys_eq!(token_account.owner, *owner, ErrorCode::BasketAccountMismatch);
+    Ok(token_account)
+}
+
+/// Loads the token program that owns the `owned` account from a remaining account
+///
+/// # Errors
+///
+/// Fails with `BasketAccountMismatch` if the account is another program.
+pub fn basket_leg_token_program<'info>(
+    account: &'info AccountInfo<'info>,
+    owned: &AccountInfo,
+) -> Result<Interface<'info, TokenInterface>> {
+    require_keys_eq!(account.key(), *owned.owner, ErrorCode::BasketAccountMismatch);
+    Interface::try_from(account)
+}
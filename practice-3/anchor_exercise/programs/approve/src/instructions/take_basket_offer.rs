@@ -0,0 +1,207 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::ErrorCode,
+    events::BasketOfferTaken,
+    instructions::{
+        shared::{
+            basket_leg_mint, basket_leg_token_account, basket_leg_token_program,
+            transfer_checked_with_hook, validate_transfer_hook_accounts,
+        },
+        take_offer::protocol_fee,
+    },
+    BasketLeg, BasketOffer, Config, SEED,
+};
+
+/// Remaining accounts of every offered leg: mint, maker token account, taker
+/// token account, token program
+pub const TAKE_OFFERED_LEG_ACCOUNTS: usize = 4;
+
+/// Remaining accounts of every wanted leg: mint, taker token account, maker
+/// token account, treasury token account, token program
+pub const TAKE_WANTED_LEG_ACCOUNTS: usize = 5;
+
+#[derive(Accounts)]
+pub struct TakeBasketOffer<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut)]
+    pub maker: SystemAccount<'info>, // gets the rent of the basket offer account back
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        seeds = [b"basket", maker.key().as_ref(), basket_offer.id.to_le_bytes().as_ref()],
+        bump = basket_offer.bump
+    )]
+    pub basket_offer: Account<'info, BasketOffer>,
+
+    #[account(seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+}
+
+/// Settles every leg of the basket offer atomically: the taker pays every
+/// wanted leg and receives every offered leg, then the basket offer account
+/// is closed to the maker.
+///
+/// Remaining accounts are the accounts of every offered leg, then of every
+/// wanted leg (see `TAKE_OFFERED_LEG_ACCOUNTS` and `TAKE_WANTED_LEG_ACCOUNTS`),
+/// followed by any extra accounts required by transfer hooks. All token
+/// accounts must already exist.
+pub fn settle_basket_offer<'info>(
+    ctx: Context<'_, '_, 'info, 'info, TakeBasketOffer<'info>>,
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+    require_keys_neq!(
+        ctx.accounts.taker.key(),
+        ctx.accounts.maker.key(),
+        ErrorCode::SelfTake
+    );
+    let basket_offer = &ctx.accounts.basket_offer;
+    require!(
+        Clock::get()?.unix_timestamp < basket_offer.expires_at,
+        ErrorCode::OfferExpired
+    );
+
+    let offered_accounts_len = basket_offer.offered.len() * TAKE_OFFERED_LEG_ACCOUNTS;
+    let wanted_accounts_len = basket_offer.wanted.len() * TAKE_WANTED_LEG_ACCOUNTS;
+    require!(
+        ctx.remaining_accounts.len() >= offered_accounts_len + wanted_accounts_len,
+        ErrorCode::BasketAccountMismatch
+    );
+    let (offered_accounts, rest) = ctx.remaining_accounts.split_at(offered_accounts_len);
+    let (wanted_accounts, hook_accounts) = rest.split_at(wanted_accounts_len);
+
+    let mut fees = Vec::with_capacity(basket_offer.wanted.len());
+    for (leg, accounts) in basket_offer
+        .wanted
+        .iter()
+        .zip(wanted_accounts.chunks_exact(TAKE_WANTED_LEG_ACCOUNTS))
+    {
+        fees.push(pay_wanted_leg(&ctx, leg, accounts, hook_accounts)?);
+    }
+    for (leg, accounts) in basket_offer
+        .offered
+        .iter()
+        .zip(offered_accounts.chunks_exact(TAKE_OFFERED_LEG_ACCOUNTS))
+    {
+        send_offered_leg(&ctx, leg, accounts, hook_accounts)?;
+    }
+
+    emit!(BasketOfferTaken {
+        basket_offer: basket_offer.key(),
+        id: basket_offer.id,
+        maker: basket_offer.maker,
+        taker: ctx.accounts.taker.key(),
+        fees,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
+
+/// Sends a wanted leg from the taker to the maker, minus the protocol fee
+/// which goes to the treasury, and returns the fee
+pub fn pay_wanted_leg<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, TakeBasketOffer<'info>>,
+    leg: &BasketLeg,
+    accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+) -> Result<u64> {
+    let [mint, taker_token_account, maker_token_account, treasury_token_account, token_program] =
+        accounts
+    else {
+        return err!(ErrorCode::BasketAccountMismatch);
+    };
+    let mint = basket_leg_mint(mint, leg)?;
+    let token_program = basket_leg_token_program(token_program, &mint.to_account_info())?;
+    let taker_token_account =
+        basket_leg_token_account(taker_token_account, &leg.mint, &ctx.accounts.taker.key())?;
+    let maker_token_account =
+        basket_leg_token_account(maker_token_account, &leg.mint, &ctx.accounts.maker.key())?;
+    let treasury_token_account =
+        basket_leg_token_account(treasury_token_account, &leg.mint, &ctx.accounts.config.treasury)?;
+    validate_transfer_hook_accounts(&mint, hook_accounts)?;
+
+    let fee = protocol_fee(leg.amount, ctx.accounts.config.fee_basis_points)?;
+    transfer_checked_with_hook(
+        &token_program,
+        taker_token_account.to_account_info(),
+        &mint,
+        maker_token_account.to_account_info(),
+        ctx.accounts.taker.to_account_info(),
+        hook_accounts,
+        leg.amount - fee,
+        &[],
+    )?;
+    if fee > 0 {
+        transfer_checked_with_hook(
+            &token_program,
+            taker_token_account.to_account_info(),
+            &mint,
+            treasury_token_account.to_account_info(),
+            ctx.accounts.taker.to_account_info(),
+            hook_accounts,
+            fee,
+            &[],
+        )?;
+    }
+    Ok(fee)
+}
+
+/// Sends an offered leg from the maker to the taker, signed by the basket
+/// offer PDA as the delegate of the maker token account
+///
+/// # Errors
+///
+/// Fails if the maker revoked the delegation or no longer has enough tokens.
+pub fn send_offered_leg<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, TakeBasketOffer<'info>>,
+    leg: &BasketLeg,
+    accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let [mint, maker_token_account, taker_token_account, token_program] = accounts else {
+        return err!(ErrorCode::BasketAccountMismatch);
+    };
+    let mint = basket_leg_mint(mint, leg)?;
+    let token_program = basket_leg_token_program(token_program, &mint.to_account_info())?;
+    let maker_token_account =
+        basket_leg_token_account(maker_token_account, &leg.mint, &ctx.accounts.maker.key())?;
+    let taker_token_account =
+        basket_leg_token_account(taker_token_account, &leg.mint, &ctx.accounts.taker.key())?;
+    validate_transfer_hook_accounts(&mint, hook_accounts)?;
+
+    let basket_offer = &ctx.accounts.basket_offer;
+    require!(
+        maker_token_account.delegate == Some(basket_offer.key()).into(),
+        ErrorCode::DelegateRevoked
+    );
+    require!(
+        maker_token_account.delegated_amount >= leg.amount,
+        ErrorCode::InsufficientDelegatedAmount
+    );
+    require!(
+        maker_token_account.amount >= leg.amount,
+        ErrorCode::InsufficientMakerBalance
+    );
+
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"basket",
+        basket_offer.maker.as_ref(),
+        &basket_offer.id.to_le_bytes()[..],
+        &[basket_offer.bump],
+    ]];
+
+    transfer_checked_with_hook(
+        &token_program,
+        maker_token_account.to_account_info(),
+        &mint,
+        taker_token_account.to_account_info(),
+        basket_offer.to_account_info(),
+        hook_accounts,
+        leg.amount,
+        &signer_seeds,
+    )
+}
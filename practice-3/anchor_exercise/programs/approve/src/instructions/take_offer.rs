@@ -1,14 +1,19 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, solana_program::hash::hashv};
 
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{
-        transfer_checked, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
-    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::Offer;
+use crate::{
+    error::ErrorCode,
+    instructions::shared::{
+        check_maker_allowance, check_taker_balance_changes, emit_offer_taken, listed_market,
+        record_fill, required_vault, send_token_a, taker_balance_b, transfer_fee,
+        validate_token_b_accounts, validate_transfer_hook_accounts, TokenBAccounts,
+    },
+    Config, Market, Offer, OfferMode, BASIS_POINTS_DENOMINATOR, SEED,
+};
 
 #[derive(Accounts)]
 pub struct TakeOffer<'info> {
@@ -18,15 +23,17 @@ pub struct TakeOffer<'info> {
     #[account(mut)]
     pub maker: SystemAccount<'info>,
 
-    pub token_mint_a: InterfaceAccount<'info, Mint>,
+    #[account(mut, mint::token_program = token_program_a)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>, // receives transfer fees withheld in a closed vault
 
+    #[account(mint::token_program = token_program_b)]
     pub token_mint_b: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = token_mint_a,
         associated_token::authority = maker,
-        associated_token::token_program = token_program,
+        associated_token::token_program = token_program_a,
     )]
     pub maker_token_account_a: Box<InterfaceAccount<'info, TokenAccount>>,
 
@@ -35,7 +42,7 @@ pub struct TakeOffer<'info> {
         payer = taker,
         associated_token::mint = token_mint_a,
         associated_token::authority = taker,
-        associated_token::token_program = token_program,
+        associated_token::token_program = token_program_a,
     )]
     pub taker_token_account_a: Box<InterfaceAccount<'info, TokenAccount>>,
     
@@ -43,82 +50,282 @@ pub struct TakeOffer<'info> {
         mut,
         associated_token::mint = token_mint_b,
         associated_token::authority = taker,
-        associated_token::token_program = token_program,
+        associated_token::token_program = token_program_b,
     )]
-    pub taker_token_account_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub taker_token_account_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // not passed for SOL offers
     
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = token_mint_b,
         associated_token::authority = maker,
-        associated_token::token_program = token_program,
+        associated_token::token_program = token_program_b,
     )]
-    pub maker_token_account_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub maker_token_account_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // not passed for SOL offers
     
     #[account(
         mut,
-        close = maker,
-        has_one = maker,
-        has_one = token_mint_a,
-        has_one = token_mint_b,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        has_one = token_mint_a @ ErrorCode::OfferMintMismatch,
+        has_one = token_mint_b @ ErrorCode::OfferMintMismatch,
         seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
         bump = offer.bump
     )]
-    offer: Account<'info, Offer>,
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", offer.key().as_ref()],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = offer,
+        token::token_program = token_program_a,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // only for OfferMode::Vault offers
+
+    #[account(seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: owner of the fee token account or receiver of SOL fees, checked against the config
+    #[account(mut, address = config.treasury @ ErrorCode::TreasuryMismatch)]
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_b,
+    )]
+    pub treasury_token_account_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // not passed for SOL offers
+
+    #[account(mut)]
+    pub market: Option<Box<Account<'info, Market>>>, // only for offers listed on a market
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Interface<'info, TokenInterface>,
+    pub token_program_a: Interface<'info, TokenInterface>, // program that owns token A
+    pub token_program_b: Interface<'info, TokenInterface>, // program that owns token B
     pub system_program: Program<'info, System>,
 }
 
 
-pub fn send_wanted_tokens_to_maker(ctx: &Context<TakeOffer>) -> Result<()> {
-    let transfer_accounts = TransferChecked {
-        from: ctx.accounts.taker_token_account_b.to_account_info(),
-        mint: ctx.accounts.token_mint_b.to_account_info(),
-        to: ctx.accounts.maker_token_account_b.to_account_info(),
-        authority: ctx.accounts.taker.to_account_info(),
-    };
+/// Fills `token_b_amount` of the offer: the taker pays token B, receives the
+/// proportional amount of token A, and the offer is closed once fully filled.
+///
+/// The taker receives at least `expected_token_a_amount` and pays at most
+/// `max_token_b_amount`, both checked against the offer terms and against the
+/// actual balance changes of the taker's token accounts. The expected token A
+/// amount is net of any Token-2022 transfer fee withheld from the taker.
+///
+/// Extra accounts required by the transfer hooks of either mint are passed
+/// as remaining accounts.
+///
+/// When token B is the native mint the taker pays in lamports with a system
+/// transfer and the token B accounts are not passed.
+pub fn fill_offer<'info>(
+    mut ctx: Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+    token_b_amount: u64,
+    expected_token_a_amount: u64,
+    max_token_b_amount: u64,
+    allowlist_proof: &[[u8; 32]],
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+    validate_taker(&ctx, allowlist_proof)?;
+    require!(
+        Clock::get()?.unix_timestamp < ctx.accounts.offer.expires_at,
+        ErrorCode::OfferExpired
+    );
 
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        transfer_accounts,
+    let token_a_amount = token_a_amount_for_fill(&ctx.accounts.offer, token_b_amount)?;
+    let token_a_received = token_a_amount
+        .checked_sub(transfer_fee(&ctx.accounts.token_mint_a, token_a_amount)?)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(
+        token_a_received >= expected_token_a_amount && token_b_amount <= max_token_b_amount,
+        ErrorCode::SlippageExceeded
     );
+    match ctx.accounts.offer.mode {
+        OfferMode::Delegation => validate_maker_allowance(&ctx, token_a_amount)?,
+        OfferMode::Vault => {
+            required_vault(&ctx.accounts.vault)?;
+        }
+    }
+    validate_transfer_hook_accounts(&ctx.accounts.token_mint_a, ctx.remaining_accounts)?;
+    validate_transfer_hook_accounts(&ctx.accounts.token_mint_b, ctx.remaining_accounts)?;
+    let accounts = &ctx.accounts;
+    validate_token_b_accounts(
+        &accounts.offer.token_mint_b,
+        &[
+            accounts.taker_token_account_b.is_some(),
+            accounts.maker_token_account_b.is_some(),
+            accounts.treasury_token_account_b.is_some(),
+        ],
+    )?;
 
-    transfer_checked(
-        cpi_ctx,
-        ctx.accounts.offer.token_b_wanted_amount,
-        ctx.accounts.token_mint_b.decimals,
-    )
+    let taker_balance_a = accounts.taker_token_account_a.amount;
+    let taker_balance_b = taker_balance_b(&accounts.taker, accounts.taker_token_account_b.as_deref());
+
+    let fee = protocol_fee(token_b_amount, accounts.config.fee_basis_points)?;
+    token_b_accounts(accounts).pay(ctx.remaining_accounts, token_b_amount, fee)?;
+    send_token_a(
+        &accounts.offer,
+        accounts.vault.as_deref(),
+        &accounts.maker_token_account_a,
+        &accounts.taker_token_account_a,
+        &accounts.token_mint_a,
+        &accounts.token_program_a,
+        ctx.remaining_accounts,
+        token_a_amount,
+    )?;
+
+    let accounts = &mut ctx.accounts;
+    check_taker_balance_changes(
+        &accounts.taker,
+        &mut accounts.taker_token_account_a,
+        accounts.taker_token_account_b.as_deref_mut(),
+        taker_balance_a,
+        taker_balance_b,
+        expected_token_a_amount,
+        max_token_b_amount,
+    )?;
+    record_taken_fill(&mut ctx, token_a_amount, token_b_amount)?;
+
+    emit_offer_taken(&ctx.accounts.offer, ctx.accounts.taker.key(), token_a_amount, token_b_amount, fee)
+}
+
+/// Checks that the taker is allowed to take the offer
+///
+/// # Errors
+///
+/// Fails if the taker is the maker, the offer is private and reserved for
+/// another wallet, or the offer has an allowlist and `allowlist_proof` does
+/// not prove the taker is on it.
+pub fn validate_taker(ctx: &Context<TakeOffer>, allowlist_proof: &[[u8; 32]]) -> Result<()> {
+    check_taker_allowed(&ctx.accounts.offer, &ctx.accounts.taker.key(), allowlist_proof)
+}
+
+/// Checks that `taker` is allowed to take `offer`, see [`validate_taker`]
+pub fn check_taker_allowed(offer: &Offer, taker: &Pubkey, allowlist_proof: &[[u8; 32]]) -> Result<()> {
+    require_keys_neq!(*taker, offer.maker, ErrorCode::SelfTake);
+
+    if let Some(allowed_taker) = offer.allowed_taker {
+        require_keys_eq!(*taker, allowed_taker, ErrorCode::TakerNotAllowed);
+    }
+    if let Some(allowlist_root) = offer.allowlist_root {
+        require!(
+            verify_allowlist_proof(&allowlist_root, allowlist_proof, taker),
+            ErrorCode::NotOnAllowlist
+        );
+    }
+    Ok(())
+}
+
+/// Verifies a Merkle proof that `taker` is a leaf of the tree with `root`.
+///
+/// Leaves are `sha256(pubkey)` and each parent is the `sha256` of its two
+/// children in ascending byte order, so proofs carry no left/right flags.
+pub fn verify_allowlist_proof(root: &[u8; 32], proof: &[[u8; 32]], taker: &Pubkey) -> bool {
+    let leaf = hashv(&[taker.as_ref()]).to_bytes();
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+    computed == *root
 }
 
-pub fn send_token_to_taker(ctx: &Context<TakeOffer>) -> Result<()> {
-    let signer_seeds: [&[&[u8]]; 1] = [&[
-        b"offer",
-        ctx.accounts.maker.to_account_info().key.as_ref(),
-        &ctx.accounts.offer.id.to_le_bytes()[..],
-        &[ctx.accounts.offer.bump],
-    ]];
-
-    let transfer_accounts = TransferChecked {
-        from: ctx.accounts.maker_token_account_a.to_account_info(),
-        mint: ctx.accounts.token_mint_a.to_account_info(),
-        to: ctx.accounts.taker_token_account_a.to_account_info(),
-        authority: ctx.accounts.offer.to_account_info(),
-    };
-
-    let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        transfer_accounts,
-        &signer_seeds,
+/// Computes how much token A the taker gets for `token_b_amount` of token B
+/// at the offer's remaining price.
+///
+/// The result is rounded down so rounding always favours the maker; filling
+/// the whole `remaining_b` always yields exactly `remaining_a`.
+pub fn token_a_amount_for_fill(offer: &Offer, token_b_amount: u64) -> Result<u64> {
+    require!(
+        token_b_amount > 0 && token_b_amount <= offer.remaining_b,
+        ErrorCode::InvalidFillAmount
     );
 
-    transfer_checked(
-        cpi_ctx,
-        ctx.accounts.offer.token_a_amount,
-        ctx.accounts.token_mint_a.decimals,
+    if token_b_amount == offer.remaining_b {
+        return Ok(offer.remaining_a);
+    }
+
+    let token_a_amount = (token_b_amount as u128)
+        .checked_mul(offer.remaining_a as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(offer.remaining_b as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let token_a_amount = u64::try_from(token_a_amount).map_err(|_| ErrorCode::MathOverflow)?;
+
+    require!(token_a_amount > 0, ErrorCode::FillTooSmall);
+    Ok(token_a_amount)
+}
+
+/// Computes the protocol fee taken from `token_b_amount`, rounded up so that
+/// splitting a take into many small partial fills can not avoid the fee
+pub fn protocol_fee(token_b_amount: u64, fee_basis_points: u16) -> Result<u64> {
+    let fee = (token_b_amount as u128)
+        .checked_mul(fee_basis_points as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .div_ceil(BASIS_POINTS_DENOMINATOR as u128);
+    let fee = u64::try_from(fee).map_err(|_| ErrorCode::MathOverflow)?;
+    Ok(fee)
+}
+
+/// Checks that the offer can still pull `token_a_amount` from the maker,
+/// see [`check_maker_allowance`]
+pub fn validate_maker_allowance(ctx: &Context<TakeOffer>, token_a_amount: u64) -> Result<()> {
+    check_maker_allowance(
+        &ctx.accounts.offer.key(),
+        &ctx.accounts.maker_token_account_a,
+        token_a_amount,
     )
+}
 
+/// Returns the accounts that move token B from the taker to the maker and
+/// the treasury
+pub fn token_b_accounts<'a, 'info>(accounts: &'a TakeOffer<'info>) -> TokenBAccounts<'a, 'info> {
+    TokenBAccounts {
+        taker: accounts.taker.to_account_info(),
+        taker_token_account: accounts.taker_token_account_b.as_ref().map(|account| account.to_account_info()),
+        maker: accounts.maker.to_account_info(),
+        maker_token_account: accounts.maker_token_account_b.as_ref().map(|account| account.to_account_info()),
+        treasury: accounts.treasury.to_account_info(),
+        treasury_token_account: accounts
+            .treasury_token_account_b
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        mint: &accounts.token_mint_b,
+        token_program: &accounts.token_program_b,
+        system_program: &accounts.system_program,
+    }
 }
 
+/// Decreases the remaining amounts, updates the offer's entry in its market
+/// and closes the offer (and its vault) to the maker when nothing is left to
+/// fill.
+pub fn record_taken_fill<'info>(
+    ctx: &mut Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> Result<()> {
+    let remaining_accounts = ctx.remaining_accounts;
+    let accounts = &mut ctx.accounts;
+    record_fill(
+        &mut accounts.offer,
+        accounts.vault.as_deref_mut(),
+        &accounts.maker_token_account_a,
+        accounts.maker.to_account_info(),
+        &accounts.token_mint_a,
+        &accounts.token_program_a,
+        remaining_accounts,
+        token_a_amount,
+        token_b_amount,
+    )?;
+
+    if let Some(market) = listed_market(&mut accounts.market, &accounts.offer)? {
+        market.sync(accounts.offer.key(), &accounts.offer);
+    }
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::ErrorCode,
+    instructions::{
+        shared::{
+            check_maker_allowance, check_taker_balance_changes, emit_offer_taken, is_native_mint,
+            listed_market, record_fill, send_token_a, taker_balance_b, validate_token_b_accounts,
+            validate_transfer_hook_accounts, vault_address, TokenBAccounts,
+        },
+        take_offer::{check_taker_allowed, protocol_fee},
+    },
+    Config, Market, Offer, OfferMode, SEED,
+};
+
+/// Remaining accounts of every listed offer: offer, maker, maker token account
+/// A, maker token account B and vault. Unused slots (token account B of SOL
+/// offers, vault of delegation offers) can hold any account, such as the
+/// program id.
+pub const TAKE_OFFERS_ACCOUNTS_PER_OFFER: usize = 5;
+
+#[derive(Accounts)]
+pub struct TakeOffers<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+
+    #[account(mut, mint::token_program = token_program_a)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>, // receives transfer fees withheld in a closed vault
+
+    #[account(mint::token_program = token_program_b)]
+    pub token_mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a,
+    )]
+    pub taker_token_account_a: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_b,
+    )]
+    pub taker_token_account_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // not passed for SOL offers
+
+    #[account(seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    /// CHECK: owner of the fee token account or receiver of SOL fees, checked against the config
+    #[account(mut, address = config.treasury @ ErrorCode::TreasuryMismatch)]
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_b,
+    )]
+    pub treasury_token_account_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // not passed for SOL offers
+
+    #[account(mut)]
+    pub market: Option<Box<Account<'info, Market>>>, // required if any listed offer is on a market
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program_a: Interface<'info, TokenInterface>, // program that owns token A
+    pub token_program_b: Interface<'info, TokenInterface>, // program that owns token B
+    pub system_program: Program<'info, System>,
+}
+
+/// Fully fills `offer_count` offers of the same pair in one instruction,
+/// failing as a whole if any of them can not be filled.
+///
+/// Remaining accounts are `TAKE_OFFERS_ACCOUNTS_PER_OFFER` accounts for
+/// every offer, followed by any extra accounts required by transfer hooks.
+/// `allowlist_proofs[i]` is the allowlist proof for the i-th offer, missing
+/// proofs are treated as empty. Maker token accounts must already exist.
+///
+/// The taker receives at least `expected_token_a_amount` and pays at most
+/// `max_token_b_amount` in total, net of Token-2022 transfer fees on token A.
+pub fn fill_offers<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, TakeOffers<'info>>,
+    offer_count: u8,
+    expected_token_a_amount: u64,
+    max_token_b_amount: u64,
+    allowlist_proofs: &[Vec<[u8; 32]>],
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+
+    let offer_accounts_len = offer_count as usize * TAKE_OFFERS_ACCOUNTS_PER_OFFER;
+    require!(
+        offer_count > 0 && ctx.remaining_accounts.len() >= offer_accounts_len,
+        ErrorCode::OfferAccountMismatch
+    );
+    let (offer_accounts, hook_accounts) = ctx.remaining_accounts.split_at(offer_accounts_len);
+    validate_transfer_hook_accounts(&ctx.accounts.token_mint_a, hook_accounts)?;
+    validate_transfer_hook_accounts(&ctx.accounts.token_mint_b, hook_accounts)?;
+
+    validate_token_b_accounts(
+        &ctx.accounts.token_mint_b.key(),
+        &[
+            ctx.accounts.taker_token_account_b.is_some(),
+            ctx.accounts.treasury_token_account_b.is_some(),
+        ],
+    )?;
+
+    let taker_balance_a = ctx.accounts.taker_token_account_a.amount;
+    let taker_balance_b = taker_balance_b(&ctx.accounts.taker, ctx.accounts.taker_token_account_b.as_deref());
+
+    for (index, accounts) in offer_accounts
+        .chunks_exact(TAKE_OFFERS_ACCOUNTS_PER_OFFER)
+        .enumerate()
+    {
+        let allowlist_proof = allowlist_proofs.get(index).map(Vec::as_slice).unwrap_or(&[]);
+        let (offer_key, offer) = fill_listed_offer(&ctx, accounts, hook_accounts, allowlist_proof)?;
+        if let Some(market) = listed_market(&mut ctx.accounts.market, &offer)? {
+            market.sync(offer_key, &offer);
+        }
+    }
+
+    let accounts = &mut ctx.accounts;
+    check_taker_balance_changes(
+        &accounts.taker,
+        &mut accounts.taker_token_account_a,
+        accounts.taker_token_account_b.as_deref_mut(),
+        taker_balance_a,
+        taker_balance_b,
+        expected_token_a_amount,
+        max_token_b_amount,
+    )
+}
+
+/// Fully fills one listed offer and closes it (and its vault) to its maker,
+/// returning the offer as filled so it can be removed from its market
+///
+/// # Errors
+///
+/// Fails if the accounts do not belong to the offer, the offer is for
+/// another pair, has expired, can not be taken by the taker, or the maker no
+/// longer has the offered tokens.
+pub fn fill_listed_offer<'info>(
+    ctx: &Context<'_, '_, 'info, 'info, TakeOffers<'info>>,
+    accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+    allowlist_proof: &[[u8; 32]],
+) -> Result<(Pubkey, Offer)> {
+    let [offer, maker, maker_token_account_a, maker_token_account_b, vault] = accounts else {
+        return err!(ErrorCode::OfferAccountMismatch);
+    };
+    let mut offer = Account::<Offer>::try_from(offer)?;
+    require_keys_eq!(maker.key(), offer.maker, ErrorCode::NotOfferMaker);
+    require_keys_eq!(
+        offer.token_mint_a,
+        ctx.accounts.token_mint_a.key(),
+        ErrorCode::OfferMintMismatch
+    );
+    require_keys_eq!(
+        offer.token_mint_b,
+        ctx.accounts.token_mint_b.key(),
+        ErrorCode::OfferMintMismatch
+    );
+    check_taker_allowed(&offer, &ctx.accounts.taker.key(), allowlist_proof)?;
+    require!(
+        Clock::get()?.unix_timestamp < offer.expires_at,
+        ErrorCode::OfferExpired
+    );
+
+    let maker_token_account_a = maker_token_account(maker_token_account_a, &offer.token_mint_a, &offer.maker)?;
+    let mut vault = match offer.mode {
+        OfferMode::Vault => {
+            require_keys_eq!(vault.key(), vault_address(&offer.key()), ErrorCode::OfferAccountMismatch);
+            Some(InterfaceAccount::<TokenAccount>::try_from(vault)?)
+        }
+        OfferMode::Delegation => {
+            check_maker_allowance(&offer.key(), &maker_token_account_a, offer.remaining_a)?;
+            None
+        }
+    };
+    let maker_token_account_b = if is_native_mint(&offer.token_mint_b) {
+        None
+    } else {
+        Some(maker_token_account(maker_token_account_b, &offer.token_mint_b, &offer.maker)?.to_account_info())
+    };
+
+    let token_a_amount = offer.remaining_a;
+    let token_b_amount = offer.remaining_b;
+    let fee = protocol_fee(token_b_amount, ctx.accounts.config.fee_basis_points)?;
+    let token_b_accounts = TokenBAccounts {
+        taker: ctx.accounts.taker.to_account_info(),
+        taker_token_account: ctx
+            .accounts
+            .taker_token_account_b
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        maker: maker.clone(),
+        maker_token_account: maker_token_account_b,
+        treasury: ctx.accounts.treasury.to_account_info(),
+        treasury_token_account: ctx
+            .accounts
+            .treasury_token_account_b
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        mint: &ctx.accounts.token_mint_b,
+        token_program: &ctx.accounts.token_program_b,
+        system_program: &ctx.accounts.system_program,
+    };
+    token_b_accounts.pay(hook_accounts, token_b_amount, fee)?;
+    send_token_a(
+        &offer,
+        vault.as_ref(),
+        &maker_token_account_a,
+        &ctx.accounts.taker_token_account_a,
+        &ctx.accounts.token_mint_a,
+        &ctx.accounts.token_program_a,
+        hook_accounts,
+        token_a_amount,
+    )?;
+
+    record_fill(
+        &mut offer,
+        vault.as_mut(),
+        &maker_token_account_a,
+        maker.clone(),
+        &ctx.accounts.token_mint_a,
+        &ctx.accounts.token_program_a,
+        hook_accounts,
+        token_a_amount,
+        token_b_amount,
+    )?;
+    emit_offer_taken(&offer, ctx.accounts.taker.key(), token_a_amount, token_b_amount, fee)?;
+    Ok((offer.key(), (*offer).clone()))
+}
+
+/// Loads a maker token account of `mint` from a remaining account
+fn maker_token_account<'info>(
+    account: &'info AccountInfo<'info>,
+    mint: &Pubkey,
+    maker: &Pubkey,
+) -> Result<InterfaceAccount<'info, TokenAccount>> {
+    let token_account = InterfaceAccount::<TokenAccount>::try_from(account)?;
+    require_keys_eq!(token_account.mint, *mint, ErrorCode::OfferAccountMismatch);
+    require_keys_eq!(token_account.owner, *maker, ErrorCode::OfferAccountMismatch);
+    Ok(token_account)
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::ErrorCode, Config, MAX_FEE_BASIS_POINTS, SEED};
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ ErrorCode::NotAdmin,
+        seeds = [SEED.as_bytes()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Replaces the admin, fee and treasury of the program config
+pub fn apply_config_update(
+    ctx: Context<UpdateConfig>,
+    admin: Pubkey,
+    fee_basis_points: u16,
+    treasury: Pubkey,
+) -> Result<()> {
+    require!(
+        fee_basis_points <= MAX_FEE_BASIS_POINTS,
+        ErrorCode::InvalidFeeBasisPoints
+    );
+
+    let config = &mut ctx.accounts.config;
+    config.admin = admin;
+    config.fee_basis_points = fee_basis_points;
+    config.treasury = treasury;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{approve, Approve, Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::ErrorCode,
+    events::OfferUpdated,
+    instructions::shared::{
+        amount_with_transfer_fee, listed_market, required_vault, transfer_checked_with_hook,
+        transfer_from_vault,
+    },
+    Config, Market, Offer, SEED,
+};
+
+#[derive(Accounts)]
+pub struct UpdateOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>, // only the maker can change the offer terms
+
+    #[account(mint::token_program = token_program)]
+    pub token_mint_a: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_token_account_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds = [SEED.as_bytes()], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+
+    #[account(
+        mut,
+        has_one = maker @ ErrorCode::NotOfferMaker,
+        has_one = token_mint_a @ ErrorCode::OfferMintMismatch,
+        seeds = [b"offer", maker.key().as_ref(), offer.id.to_le_bytes().as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", offer.key().as_ref()],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = offer,
+        token::token_program = token_program,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>, // only for OfferMode::Vault offers
+
+    #[account(mut)]
+    pub market: Option<Box<Account<'info, Market>>>, // only for offers listed on a market
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Checks the new offer terms
+///
+/// # Errors
+///
+/// Fails if the program is paused, either amount is zero or the offer has
+/// already expired.
+pub fn validate_update(
+    ctx: &Context<UpdateOffer>,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+) -> Result<()> {
+    require!(!ctx.accounts.config.paused, ErrorCode::ProgramPaused);
+    require!(
+        token_a_offered_amount > 0 && token_b_wanted_amount > 0,
+        ErrorCode::ZeroAmount
+    );
+    require!(
+        Clock::get()?.unix_timestamp < ctx.accounts.offer.expires_at,
+        ErrorCode::OfferExpired
+    );
+    Ok(())
+}
+
+/// Re-approves the offer PDA as delegate for the new token A amount
+pub fn redelegate_offered_tokens(ctx: &Context<UpdateOffer>, token_a_offered_amount: u64) -> Result<()> {
+    let delegate_accounts = Approve {
+        to: ctx.accounts.maker_token_account_a.to_account_info(),
+        delegate: ctx.accounts.offer.to_account_info(),
+        authority: ctx.accounts.maker.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), delegate_accounts);
+
+    approve(cpi_ctx, token_a_offered_amount)
+}
+
+/// Tops up or refunds the vault so it holds the new token A amount, the maker
+/// pays the transfer fee of a top-up
+pub fn rebalance_vault<'info>(
+    ctx: &Context<'_, '_, '_, 'info, UpdateOffer<'info>>,
+    token_a_offered_amount: u64,
+) -> Result<()> {
+    let vault = required_vault(&ctx.accounts.vault)?;
+    let remaining_a = ctx.accounts.offer.remaining_a;
+
+    if token_a_offered_amount < remaining_a {
+        return transfer_from_vault(
+            &ctx.accounts.offer,
+            vault,
+            &ctx.accounts.maker_token_account_a,
+            &ctx.accounts.token_mint_a,
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            remaining_a - token_a_offered_amount,
+        );
+    }
+    if token_a_offered_amount == remaining_a {
+        return Ok(());
+    }
+
+    let deposit_amount = amount_with_transfer_fee(
+        &ctx.accounts.token_mint_a,
+        token_a_offered_amount - remaining_a,
+    )?;
+
+    transfer_checked_with_hook(
+        &ctx.accounts.token_program,
+        ctx.accounts.maker_token_account_a.to_account_info(),
+        &ctx.accounts.token_mint_a,
+        vault.to_account_info(),
+        ctx.accounts.maker.to_account_info(),
+        ctx.remaining_accounts,
+        deposit_amount,
+        &[],
+    )
+}
+
+/// Stores the new terms, the offer keeps its PDA, id and bump.
+///
+/// Any previous partial fills are forgotten: the new amounts become both the
+/// offer terms and what is left to fill. An offer listed on a market moves
+/// to its new price in the order book.
+pub fn save_updated_offer(
+    ctx: Context<UpdateOffer>,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+) -> Result<()> {
+    let offer = &mut ctx.accounts.offer;
+    offer.token_a_amount = token_a_offered_amount;
+    offer.token_b_wanted_amount = token_b_wanted_amount;
+    offer.remaining_a = token_a_offered_amount;
+    offer.remaining_b = token_b_wanted_amount;
+
+    if let Some(market) = listed_market(&mut ctx.accounts.market, &ctx.accounts.offer)? {
+        market.sync(ctx.accounts.offer.key(), &ctx.accounts.offer);
+    }
+    let offer = &ctx.accounts.offer;
+
+    emit!(OfferUpdated {
+        offer: offer.key(),
+        id: offer.id,
+        maker: offer.maker,
+        token_a_amount: token_a_offered_amount,
+        token_b_wanted_amount,
+        slot: Clock::get()?.slot,
+    });
+    Ok(())
+}
@@ -1,5 +1,6 @@
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod instructions;
 pub mod state;
 
@@ -15,21 +16,149 @@ declare_id!("8a22g25Y8s4resVBNyqQzUn8fSThTTi6i7uEDxyn6fVp");
 pub mod approve {
     use super::*;
 
-    pub fn make_offer(
-        context: Context<MakeOffer>,
-        id: u64,
+    pub fn initialize(
+        context: Context<Initialize>,
+        fee_basis_points: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        instructions::initialize::handler(context, fee_basis_points, treasury)
+    }
+
+    pub fn make_offer<'info>(
+        context: Context<'_, '_, '_, 'info, MakeOffer<'info>>,
         token_a_offered_amount: u64,
         token_b_wanted_amount: u64,
+        expires_at: i64,
+        mode: OfferMode,
+        allowed_taker: Option<Pubkey>,
+        allowlist_root: Option<[u8; 32]>,
+    ) -> Result<()> {
+        instructions::make_offer::validate_offer(&context, token_a_offered_amount, token_b_wanted_amount, expires_at, mode)?;
+        match mode {
+            OfferMode::Delegation => instructions::make_offer::delegate_offered_tokens_to_vault(&context, token_a_offered_amount)?,
+            OfferMode::Vault => instructions::make_offer::deposit_offered_tokens_to_vault(&context, token_a_offered_amount)?,
+        }
+        instructions::make_offer::save_offer(context, token_a_offered_amount, token_b_wanted_amount, expires_at, mode, allowed_taker, allowlist_root)
+    }
+
+
+    pub fn take_offer<'info>(
+        context: Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+        expected_token_a_amount: u64,
+        max_token_b_amount: u64,
+        allowlist_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let token_b_amount = context.accounts.offer.remaining_b;
+        instructions::take_offer::fill_offer(
+            context,
+            token_b_amount,
+            expected_token_a_amount,
+            max_token_b_amount,
+            &allowlist_proof,
+        )
+    }
+
+    pub fn take_offer_partial<'info>(
+        context: Context<'_, '_, '_, 'info, TakeOffer<'info>>,
+        token_b_amount: u64,
+        expected_token_a_amount: u64,
+        allowlist_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        instructions::take_offer::fill_offer(
+            context,
+            token_b_amount,
+            expected_token_a_amount,
+            token_b_amount,
+            &allowlist_proof,
+        )
+    }
+
+    pub fn take_offers<'info>(
+        context: Context<'_, '_, 'info, 'info, TakeOffers<'info>>,
+        offer_count: u8,
+        expected_token_a_amount: u64,
+        max_token_b_amount: u64,
+        allowlist_proofs: Vec<Vec<[u8; 32]>>,
+    ) -> Result<()> {
+        instructions::take_offers::fill_offers(
+            context,
+            offer_count,
+            expected_token_a_amount,
+            max_token_b_amount,
+            &allowlist_proofs,
+        )
+    }
+
+    pub fn update_offer<'info>(
+        context: Context<'_, '_, '_, 'info, UpdateOffer<'info>>,
+        token_a_offered_amount: u64,
+        token_b_wanted_amount: u64,
+    ) -> Result<()> {
+        instructions::update_offer::validate_update(&context, token_a_offered_amount, token_b_wanted_amount)?;
+        match context.accounts.offer.mode {
+            OfferMode::Delegation => instructions::update_offer::redelegate_offered_tokens(&context, token_a_offered_amount)?,
+            OfferMode::Vault => instructions::update_offer::rebalance_vault(&context, token_a_offered_amount)?,
+        }
+        instructions::update_offer::save_updated_offer(context, token_a_offered_amount, token_b_wanted_amount)
+    }
+
+    pub fn cancel_offer<'info>(context: Context<'_, '_, '_, 'info, CancelOffer<'info>>) -> Result<()> {
+        instructions::cancel_offer::release_offered_tokens(context)
+    }
+
+    pub fn update_config(
+        context: Context<UpdateConfig>,
+        admin: Pubkey,
+        fee_basis_points: u16,
+        treasury: Pubkey,
     ) -> Result<()> {
-        instructions::make_offer::delegate_offered_tokens_to_vault(&context, token_a_offered_amount)?;
-        instructions::make_offer::save_offer(context, id, token_a_offered_amount, token_b_wanted_amount)
+        instructions::update_config::apply_config_update(context, admin, fee_basis_points, treasury)
+    }
+
+    pub fn set_paused(context: Context<SetPaused>, paused: bool) -> Result<()> {
+        instructions::set_paused::apply_pause(context, paused)
     }
 
+    pub fn close_expired_offer<'info>(
+        mut context: Context<'_, '_, '_, 'info, CloseExpiredOffer<'info>>,
+    ) -> Result<()> {
+        instructions::close_expired_offer::check_offer_expired(&context)?;
+        instructions::close_expired_offer::refund_expired_vault(&mut context)?;
+        instructions::close_expired_offer::delist_expired_offer(&mut context)?;
+        instructions::close_expired_offer::emit_offer_closed(&context)
+    }
 
-    pub fn take_offer(context: Context<TakeOffer>) -> Result<()> {
-        instructions::take_offer::send_wanted_tokens_to_maker(&context)?;
-        instructions::take_offer::send_token_to_taker(&context)
+    pub fn create_market(context: Context<CreateMarket>) -> Result<()> {
+        instructions::create_market::open_market(context)
     }
 
+    pub fn make_basket_offer<'info>(
+        context: Context<'_, '_, 'info, 'info, MakeBasketOffer<'info>>,
+        offered: Vec<BasketLeg>,
+        wanted: Vec<BasketLeg>,
+        expires_at: i64,
+    ) -> Result<()> {
+        instructions::make_basket_offer::validate_basket_offer(&context, &offered, &wanted, expires_at)?;
+        instructions::make_basket_offer::delegate_basket_legs(&context, &offered)?;
+        instructions::make_basket_offer::save_basket_offer(context, offered, wanted, expires_at)
+    }
+
+    pub fn take_basket_offer<'info>(
+        context: Context<'_, '_, 'info, 'info, TakeBasketOffer<'info>>,
+    ) -> Result<()> {
+        instructions::take_basket_offer::settle_basket_offer(context)
+    }
+
+    pub fn cancel_basket_offer<'info>(
+        context: Context<'_, '_, 'info, 'info, CancelBasketOffer<'info>>,
+    ) -> Result<()> {
+        instructions::cancel_basket_offer::revoke_basket_delegations(context)
+    }
+
+    pub fn close_expired_basket_offer<'info>(
+        context: Context<'_, '_, 'info, 'info, CloseExpiredBasketOffer<'info>>,
+    ) -> Result<()> {
+        instructions::close_expired_basket_offer::close_expired_basket(context)
+    }
 
 }
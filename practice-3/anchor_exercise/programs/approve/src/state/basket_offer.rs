@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::ANCHOR_DISCRIMINATOR;
+
+/// One mint of a basket offer and its amount
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Offer of several mints for several other mints, settled in one transaction
+///
+/// Offered tokens stay with the maker, the basket offer PDA is approved as
+/// the delegate of every offered leg. The account is sized for its actual
+/// number of legs, see [`BasketOffer::space`].
+#[account]
+pub struct BasketOffer {
+    pub id: u64,
+    pub maker: Pubkey,
+    pub offered: Vec<BasketLeg>, // sent from the maker to the taker
+    pub wanted: Vec<BasketLeg>, // paid by the taker to the maker, minus the protocol fee
+    pub expires_at: i64, // unix timestamp after which the offer can no longer be taken
+    pub bump: u8,
+}
+
+impl BasketOffer {
+    /// Account size, including the discriminator, for the given number of legs
+    pub fn space(offered_legs: usize, wanted_legs: usize) -> usize {
+        ANCHOR_DISCRIMINATOR
+            + 8 // id
+            + 32 // maker
+            + 4 + offered_legs * BasketLeg::INIT_SPACE
+            + 4 + wanted_legs * BasketLeg::INIT_SPACE
+            + 8 // expires_at
+            + 1 // bump
+    }
+}
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+/// Global program state created once by `initialize`
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey, // can change the config
+    pub fee_basis_points: u16, // protocol fee taken from the token B leg of every fill
+    pub treasury: Pubkey, // owner of the token accounts receiving the fees
+    pub paused: bool,
+    pub offer_count: u64, // number of offers ever made
+    pub bump: u8,
+}
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+/// Per-maker state created by the maker's first offer
+///
+/// Offers keep their `[b"offer", maker, id]` seeds, with ids taken from
+/// `offer_nonce`, so clients derive the next offer address from this account.
+#[account]
+#[derive(InitSpace)]
+pub struct MakerState {
+    pub maker: Pubkey,
+    pub offer_nonce: u64, // id of the maker's next offer or basket offer
+    pub bump: u8,
+}
@@ -0,0 +1,138 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::ErrorCode, Offer, MAX_ORDERS_PER_SIDE};
+
+/// An offer listed on a market, with its remaining amounts in base and quote tokens
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub struct OrderEntry {
+    pub offer: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+impl OrderEntry {
+    /// Whether this entry has a lower price (quote per base) than `other`
+    fn cheaper_than(&self, other: &OrderEntry) -> bool {
+        (self.quote_amount as u128) * (other.base_amount as u128)
+            < (other.quote_amount as u128) * (self.base_amount as u128)
+    }
+}
+
+/// Order book of a token pair, created once per pair by `create_market`
+///
+/// The base mint is the smaller of the two mints, so both directions of a
+/// pair share one market. Offers selling the base mint are asks, sorted by
+/// ascending price; offers buying it are bids, sorted by descending price.
+/// Orders with the same price keep their listing order. An offer evicted
+/// from a full side keeps its `market` and can still be taken or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct Market {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    #[max_len(MAX_ORDERS_PER_SIDE)]
+    pub bids: Vec<OrderEntry>, // best (highest) price first
+    #[max_len(MAX_ORDERS_PER_SIDE)]
+    pub asks: Vec<OrderEntry>, // best (lowest) price first
+    pub bump: u8,
+}
+
+impl Market {
+    /// Returns the base and quote mints of the market of a pair
+    pub fn canonical_pair(mint_a: Pubkey, mint_b: Pubkey) -> (Pubkey, Pubkey) {
+        if mint_a < mint_b {
+            (mint_a, mint_b)
+        } else {
+            (mint_b, mint_a)
+        }
+    }
+
+    /// Checks that the offer trades the pair of this market
+    pub fn validate_pair(&self, offer: &Offer) -> Result<()> {
+        require!(
+            Market::canonical_pair(offer.token_mint_a, offer.token_mint_b)
+                == (self.base_mint, self.quote_mint),
+            ErrorCode::MarketMismatch
+        );
+        Ok(())
+    }
+
+    /// Lists a new offer at its price
+    ///
+    /// When its side of the book is full, the worst priced order is evicted
+    /// to make room for a better priced offer, so dust orders can not lock
+    /// the book.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `MarketFull` if the side is full and the offer is not
+    /// priced better than the worst order on it.
+    pub fn list(&mut self, offer_key: Pubkey, offer: &Offer) -> Result<()> {
+        self.remove(offer_key);
+        require!(self.insert(offer_key, offer), ErrorCode::MarketFull);
+        Ok(())
+    }
+
+    /// Updates the entry of the offer after a fill or a reprice, removing it
+    /// once nothing is left to fill
+    ///
+    /// An offer that was evicted from a full side is listed again only if it
+    /// now beats the worst order on it.
+    pub fn sync(&mut self, offer_key: Pubkey, offer: &Offer) {
+        self.remove(offer_key);
+        self.insert(offer_key, offer);
+    }
+
+    /// Inserts the order of the offer at its price, evicting the worst order
+    /// of a full side if the offer beats it
+    ///
+    /// Returns whether the offer fits, an offer with nothing left to fill
+    /// always does.
+    fn insert(&mut self, offer_key: Pubkey, offer: &Offer) -> bool {
+        if offer.remaining_a == 0 || offer.remaining_b == 0 {
+            return true;
+        }
+
+        let is_ask = offer.token_mint_a == self.base_mint;
+        let entry = if is_ask {
+            OrderEntry {
+                offer: offer_key,
+                base_amount: offer.remaining_a,
+                quote_amount: offer.remaining_b,
+            }
+        } else {
+            OrderEntry {
+                offer: offer_key,
+                base_amount: offer.remaining_b,
+                quote_amount: offer.remaining_a,
+            }
+        };
+        let better = |entry: &OrderEntry, other: &OrderEntry| {
+            if is_ask {
+                entry.cheaper_than(other)
+            } else {
+                other.cheaper_than(entry)
+            }
+        };
+
+        let side = if is_ask { &mut self.asks } else { &mut self.bids };
+        if side.len() >= MAX_ORDERS_PER_SIDE {
+            match side.last() {
+                Some(worst) if better(&entry, worst) => side.truncate(MAX_ORDERS_PER_SIDE - 1),
+                _ => return false,
+            }
+        }
+        let position = side
+            .iter()
+            .position(|other| better(&entry, other))
+            .unwrap_or(side.len());
+        side.insert(position, entry);
+        true
+    }
+
+    /// Removes the offer from the book, if it is listed
+    pub fn remove(&mut self, offer_key: Pubkey) {
+        self.bids.retain(|entry| entry.offer != offer_key);
+        self.asks.retain(|entry| entry.offer != offer_key);
+    }
+}
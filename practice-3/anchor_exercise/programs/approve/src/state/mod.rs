@@ -1,2 +1,14 @@
 pub mod offer;
-pub use offer::*;
\ No newline at end of file
+pub use offer::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod basket_offer;
+pub use basket_offer::*;
+
+pub mod market;
+pub use market::*;
+
+pub mod maker_state;
+pub use maker_state::*;
@@ -1,5 +1,14 @@
 use anchor_lang::prelude::*;
 
+/// How the offered token A is held until the offer is taken
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum OfferMode {
+    /// Token A stays with the maker, the offer PDA is approved as its delegate
+    Delegation,
+    /// Token A is moved into a vault token account owned by the offer PDA
+    Vault,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Offer {
@@ -9,5 +18,12 @@ pub struct Offer {
     pub token_mint_b: Pubkey,
     pub token_a_amount: u64,
     pub token_b_wanted_amount: u64,
+    pub remaining_a: u64, // token A not yet sent to takers
+    pub remaining_b: u64, // token B still wanted for remaining_a
+    pub expires_at: i64, // unix timestamp after which the offer can no longer be taken
+    pub mode: OfferMode,
+    pub allowed_taker: Option<Pubkey>, // the only wallet that can take a private offer
+    pub allowlist_root: Option<[u8; 32]>, // Merkle root of the wallets that can take the offer
+    pub market: Option<Pubkey>, // market whose order book lists the offer
     pub bump: u8,
 }
@@ -0,0 +1,813 @@
+//! Fixtures and instruction builders shared by the integration tests
+//!
+//! The tests run the program on a `solana-program-test` bank. Under
+//! `cargo test-sbf` it loads `target/deploy/approve.so` from `SBF_OUT_DIR`,
+//! under plain `cargo test` it runs the program natively.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use anchor_lang::{
+    error::Error,
+    solana_program::{
+        account_info::AccountInfo,
+        clock::Clock,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        native_token::LAMPORTS_PER_SOL,
+        program_error::ProgramError, program_option::COption,
+        program_pack::Pack, pubkey::Pubkey, rent::Rent, system_program,
+    },
+    AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id},
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            default_account_state::DefaultAccountState,
+            permanent_delegate::PermanentDelegate,
+            transfer_fee::{TransferFee, TransferFeeConfig},
+            transfer_hook::TransferHook,
+            BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensions,
+            StateWithExtensionsMut,
+        },
+        state::{Account as TokenAccountState, AccountState, Mint as MintState},
+    },
+};
+use approve::{Config, MakerState, OfferMode, SEED};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_transfer_hook_interface::get_extra_account_metas_address;
+use tokio::runtime::Runtime;
+
+/// Native entrypoint of the program for `processor!`
+///
+/// Anchor's `entry` ties the account infos to the lifetime of their slice,
+/// which outlives this call only once leaked.
+fn process_instruction<'info>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    approve::entry(program_id, accounts, data)
+}
+
+/// Program id the SPL transfer hook example runs under
+pub const TRANSFER_HOOK_PROGRAM_ID: Pubkey = pubkey!("TokenHookExampLe8smaVNrxTBezWTRbEwxwb1Zykrb");
+
+/// Blocking handle on a program-test bank with the program, the token
+/// programs it calls and the SPL transfer hook example
+///
+/// Wallets are keypairs kept by the handle, so `process` signs every
+/// instruction with the wallets it names as signers.
+pub struct TestRuntime {
+    runtime: Runtime,
+    context: ProgramTestContext,
+    wallets: HashMap<Pubkey, Keypair>,
+}
+
+impl TestRuntime {
+    pub fn new() -> Self {
+        let runtime = Runtime::new().expect("tokio runtime");
+        let mut program_test = ProgramTest::new("approve", approve::ID, processor!(process_instruction));
+        // Only the program is built to SBF_OUT_DIR, the hook always runs natively
+        program_test.prefer_bpf(false);
+        program_test.add_program(
+            "spl_transfer_hook_example",
+            TRANSFER_HOOK_PROGRAM_ID,
+            processor!(spl_transfer_hook_example::processor::process),
+        );
+        let context = runtime.block_on(program_test.start_with_context());
+        Self {
+            runtime,
+            context,
+            wallets: HashMap::new(),
+        }
+    }
+
+    /// Returns the account, `None` if it does not exist or was closed
+    pub fn account(&self, key: &Pubkey) -> Option<Account> {
+        self.runtime
+            .block_on(self.context.banks_client.clone().get_account(*key))
+            .expect("get account")
+    }
+
+    /// Creates a system account holding `sol` SOL whose keypair signs for it
+    pub fn create_wallet(&mut self, sol: u64) -> Pubkey {
+        let wallet = Keypair::new();
+        let key = wallet.pubkey();
+        self.set_account(key, sol * LAMPORTS_PER_SOL, system_program::ID, Vec::new());
+        self.wallets.insert(key, wallet);
+        key
+    }
+
+    /// Funds `key` with `sol` SOL as a system account, for accounts another
+    /// program allocates and assigns to itself
+    pub fn fund(&mut self, key: Pubkey, sol: u64) {
+        self.set_account(key, sol * LAMPORTS_PER_SOL, system_program::ID, Vec::new());
+    }
+
+    /// Creates a rent-exempt account owned by `owner` holding `data`
+    pub fn create_account_with_data(&mut self, key: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let lamports = Rent::default().minimum_balance(data.len());
+        self.set_account(key, lamports, owner, data);
+    }
+
+    fn set_account(&mut self, key: Pubkey, lamports: u64, owner: Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.context.set_account(&key, &account.into());
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |account| account.lamports)
+    }
+
+    /// Deserializes an Anchor account, `None` if it does not exist or was closed
+    pub fn anchor_account<T: AccountDeserialize>(&self, key: &Pubkey) -> Option<T> {
+        let account = self.account(key)?;
+        T::try_deserialize(&mut account.data.as_slice()).ok()
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.runtime
+            .block_on(self.context.banks_client.clone().get_sysvar::<Clock>())
+            .expect("clock sysvar")
+    }
+
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        let clock = Clock {
+            unix_timestamp,
+            ..self.clock()
+        };
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Processes the instruction in its own transaction, returning the error
+    /// of the failed instruction
+    ///
+    /// Every transaction runs in a new slot with a fresh blockhash, so
+    /// repeating an instruction runs it again instead of failing as already
+    /// processed.
+    pub fn process(&mut self, instruction: &Instruction) -> Result<(), ProgramError> {
+        let slot = self.clock().slot;
+        self.context.warp_to_slot(slot + 1).expect("warp to the next slot");
+        let blockhash = self
+            .runtime
+            .block_on(self.context.banks_client.get_latest_blockhash())
+            .expect("blockhash");
+        let mut signers = vec![&self.context.payer];
+        signers.extend(
+            instruction
+                .accounts
+                .iter()
+                .filter(|meta| meta.is_signer)
+                .filter_map(|meta| self.wallets.get(&meta.pubkey)),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.context.payer.pubkey()),
+            &signers,
+            blockhash,
+        );
+
+        match self
+            .runtime
+            .block_on(self.context.banks_client.process_transaction(transaction))
+        {
+            Ok(()) => Ok(()),
+            Err(BanksClientError::TransactionError(TransactionError::InstructionError(_, error))) => {
+                Err(program_error(error))
+            }
+            Err(error) => panic!("transaction failed: {error}"),
+        }
+    }
+}
+
+impl Default for TestRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn program_error(error: InstructionError) -> ProgramError {
+    ProgramError::try_from(error.clone()).unwrap_or_else(|_| panic!("instruction failed: {error}"))
+}
+
+/// Offers made by the fixtures expire one hour after the runtime clock
+pub const OFFER_DURATION: i64 = 3_600;
+
+/// Converts a program or Anchor error code to the error the runtime returns
+pub fn anchor_error(code: impl Into<Error>) -> ProgramError {
+    code.into().into()
+}
+
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[SEED.as_bytes()], &approve::ID).0
+}
+
+pub fn maker_state_address(maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"maker", maker.as_ref()], &approve::ID).0
+}
+
+pub fn offer_address(maker: &Pubkey, id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer", maker.as_ref(), &id.to_le_bytes()], &approve::ID).0
+}
+
+pub fn token_account_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, &spl_token::ID)
+}
+
+/// The mints of an offer and the token programs that own them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pair {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_program_a: Pubkey,
+    pub token_program_b: Pubkey,
+}
+
+impl Pair {
+    /// A pair of spl-token mints
+    pub fn new(mint_a: Pubkey, mint_b: Pubkey) -> Self {
+        Self {
+            mint_a,
+            mint_b,
+            token_program_a: spl_token::ID,
+            token_program_b: spl_token::ID,
+        }
+    }
+
+    pub fn token_account_a(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint_a, &self.token_program_a)
+    }
+
+    pub fn token_account_b(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint_b, &self.token_program_b)
+    }
+}
+
+/// Vault PDA token account holding token A of a vault mode offer
+pub fn vault_address(offer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", offer.as_ref()], &approve::ID).0
+}
+
+/// Returns the id the maker's next offer gets from its offer nonce
+pub fn next_offer_id(runtime: &TestRuntime, maker: &Pubkey) -> u64 {
+    runtime
+        .anchor_account::<MakerState>(&maker_state_address(maker))
+        .map_or(0, |maker_state| maker_state.offer_nonce)
+}
+
+/// Creates an spl-token mint with no freeze authority
+pub fn create_mint(runtime: &mut TestRuntime, decimals: u8) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply: u64::MAX / 2,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    runtime.create_account_with_data(mint, spl_token::ID, data);
+    mint
+}
+
+/// Token-2022 mint extensions of the test mints
+#[derive(Clone, Copy, Debug)]
+pub enum MintExtension {
+    TransferFee { basis_points: u16, maximum_fee: u64 },
+    PermanentDelegate,
+    DefaultFrozen,
+    /// Runs the SPL transfer hook example on every transfer, see [`init_transfer_hook`]
+    TransferHook,
+}
+
+/// Creates a Token-2022 mint with the extensions
+pub fn create_token_2022_mint(runtime: &mut TestRuntime, decimals: u8, extensions: &[MintExtension]) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let extension_types: Vec<_> = extensions
+        .iter()
+        .map(|extension| match extension {
+            MintExtension::TransferFee { .. } => ExtensionType::TransferFeeConfig,
+            MintExtension::PermanentDelegate => ExtensionType::PermanentDelegate,
+            MintExtension::DefaultFrozen => ExtensionType::DefaultAccountState,
+            MintExtension::TransferHook => ExtensionType::TransferHook,
+        })
+        .collect();
+    let len = ExtensionType::try_calculate_account_len::<MintState>(&extension_types).expect("mint length");
+    let mut data = vec![0; len];
+    let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).expect("mint data");
+    for extension in extensions {
+        match *extension {
+            MintExtension::TransferFee {
+                basis_points,
+                maximum_fee,
+            } => {
+                let transfer_fee = TransferFee {
+                    epoch: 0.into(),
+                    maximum_fee: maximum_fee.into(),
+                    transfer_fee_basis_points: basis_points.into(),
+                };
+                let config = state.init_extension::<TransferFeeConfig>(true).expect("transfer fee");
+                config.older_transfer_fee = transfer_fee;
+                config.newer_transfer_fee = transfer_fee;
+            }
+            MintExtension::PermanentDelegate => {
+                state.init_extension::<PermanentDelegate>(true).expect("permanent delegate").delegate =
+                    Some(Pubkey::new_unique()).try_into().expect("delegate");
+            }
+            MintExtension::DefaultFrozen => {
+                state.init_extension::<DefaultAccountState>(true).expect("default state").state =
+                    AccountState::Frozen as u8;
+            }
+            MintExtension::TransferHook => {
+                state.init_extension::<TransferHook>(true).expect("transfer hook").program_id =
+                    Some(TRANSFER_HOOK_PROGRAM_ID).try_into().expect("hook program");
+            }
+        }
+    }
+    state.base = MintState {
+        mint_authority: COption::Some(runtime.create_wallet(1)),
+        supply: u64::MAX / 2,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::Some(Pubkey::new_unique()),
+    };
+    state.pack_base();
+    state.init_account_type().expect("mint account type");
+    runtime.create_account_with_data(mint, spl_token_2022::ID, data);
+    mint
+}
+
+/// Creates the validation account of the transfer hook example for `mint`,
+/// without extra accounts, returns the accounts a transfer of `mint` needs
+pub fn init_transfer_hook(runtime: &mut TestRuntime, mint: &Pubkey) -> Vec<AccountMeta> {
+    let mint_account = runtime.account(mint).expect("mint account");
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_account.data).expect("mint state");
+    let authority = mint_state.base.mint_authority.expect("mint authority");
+    let validation = get_extra_account_metas_address(mint, &TRANSFER_HOOK_PROGRAM_ID);
+
+    runtime.fund(validation, 1);
+    let initialize = spl_transfer_hook_interface::instruction::initialize_extra_account_meta_list(
+        &TRANSFER_HOOK_PROGRAM_ID,
+        &validation,
+        mint,
+        &authority,
+        &[],
+    );
+    runtime.process(&initialize).expect("initialize the hook validation account");
+
+    vec![
+        AccountMeta::new_readonly(TRANSFER_HOOK_PROGRAM_ID, false),
+        AccountMeta::new_readonly(validation, false),
+    ]
+}
+
+/// Creates the associated token account of `owner` holding `amount` of `mint`,
+/// with the extensions a Token-2022 mint requires of its accounts
+pub fn create_token_account(
+    runtime: &mut TestRuntime,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let mint_account = runtime.account(mint).expect("mint account");
+    let token_program = mint_account.owner;
+    let address = get_associated_token_address_with_program_id(owner, mint, &token_program);
+    let base = TokenAccountState {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccountState::default()
+    };
+    if token_program == spl_token::ID {
+        let mut data = vec![0; TokenAccountState::LEN];
+        base.pack_into_slice(&mut data);
+        runtime.create_account_with_data(address, token_program, data);
+        return address;
+    }
+
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_account.data).expect("mint state");
+    let mut extension_types =
+        ExtensionType::get_required_init_account_extensions(&mint_state.get_extension_types().expect("extensions"));
+    extension_types.push(ExtensionType::ImmutableOwner);
+    let len = ExtensionType::try_calculate_account_len::<TokenAccountState>(&extension_types)
+        .expect("token account length");
+    let mut data = vec![0; len];
+    let mut state =
+        StateWithExtensionsMut::<TokenAccountState>::unpack_uninitialized(&mut data).expect("token account data");
+    for extension_type in extension_types {
+        state
+            .init_account_extension_from_type(extension_type)
+            .expect("token account extension");
+    }
+    state.base = base;
+    state.pack_base();
+    state.init_account_type().expect("token account type");
+    runtime.create_account_with_data(address, token_program, data);
+    address
+}
+
+/// Returns the token account state of either token program, `None` if it
+/// does not exist
+pub fn token_account(runtime: &TestRuntime, address: &Pubkey) -> Option<TokenAccountState> {
+    let account = runtime.account(address)?;
+    StateWithExtensions::<TokenAccountState>::unpack(&account.data)
+        .ok()
+        .map(|state| state.base)
+}
+
+/// Returns the transfer fee withheld from the recipient of `amount` of
+/// `mint`, zero for mints without a transfer fee
+pub fn transfer_fee(runtime: &TestRuntime, mint: &Pubkey, amount: u64) -> u64 {
+    let mint_account = runtime.account(mint).expect("mint account");
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_account.data).expect("mint state");
+    mint_state
+        .get_extension::<TransferFeeConfig>()
+        .map_or(0, |config| {
+            config
+                .calculate_epoch_fee(runtime.clock().epoch, amount)
+                .expect("transfer fee")
+        })
+}
+
+/// Returns the transfer fees harvested to the mint
+pub fn withheld_mint_fees(runtime: &TestRuntime, mint: &Pubkey) -> u64 {
+    let mint_account = runtime.account(mint).expect("mint account");
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_account.data).expect("mint state");
+    mint_state
+        .get_extension::<TransferFeeConfig>()
+        .map_or(0, |config| config.withheld_amount.into())
+}
+
+/// Sums the balances of the token accounts of `mint` of the owners
+pub fn token_supply(runtime: &TestRuntime, mint: &Pubkey, owners: &[Pubkey]) -> u64 {
+    owners
+        .iter()
+        .map(|owner| token_balance(runtime, &token_account_address(owner, mint)))
+        .sum()
+}
+
+/// Returns the token balance of the account, zero if it does not exist
+pub fn token_balance(runtime: &TestRuntime, address: &Pubkey) -> u64 {
+    token_account(runtime, address).map_or(0, |account| account.amount)
+}
+
+/// Writes the config `initialize` would create
+///
+/// `initialize` only accepts the upgrade authority of the program, and the
+/// test bank loads the program without an upgradeable program data account.
+pub fn create_config(runtime: &mut TestRuntime, admin: &Pubkey, fee_basis_points: u16, treasury: &Pubkey) {
+    let (address, bump) = Pubkey::find_program_address(&[SEED.as_bytes()], &approve::ID);
+    let config = Config {
+        admin: *admin,
+        fee_basis_points,
+        treasury: *treasury,
+        paused: false,
+        offer_count: 0,
+        bump,
+    };
+    let mut data = Vec::new();
+    config.try_serialize(&mut data).expect("serialize config");
+    runtime.create_account_with_data(address, approve::ID, data);
+}
+
+/// Builds a `make_offer` of the pair for the offer `id`, listed on `market`
+/// if given
+#[allow(clippy::too_many_arguments)]
+pub fn make_offer_ix(
+    maker: &Pubkey,
+    pair: &Pair,
+    id: u64,
+    token_a_offered_amount: u64,
+    token_b_wanted_amount: u64,
+    expires_at: i64,
+    mode: OfferMode,
+    market: Option<Pubkey>,
+) -> Instruction {
+    let offer = offer_address(maker, id);
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::MakeOffer {
+            maker: *maker,
+            token_mint_a: pair.mint_a,
+            token_mint_b: pair.mint_b,
+            maker_token_account_a: pair.token_account_a(maker),
+            config: config_address(),
+            maker_state: maker_state_address(maker),
+            offer,
+            vault: (mode == OfferMode::Vault).then(|| vault_address(&offer)),
+            market,
+            associated_token_program: associated_token::ID,
+            token_program_a: pair.token_program_a,
+            token_program_b: pair.token_program_b,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::MakeOffer {
+            token_a_offered_amount,
+            token_b_wanted_amount,
+            expires_at,
+            mode,
+            allowed_taker: None,
+            allowlist_root: None,
+        }
+        .data(),
+    }
+}
+
+/// Accounts of `take_offer` and `take_offer_partial` for an offer of the
+/// pair, listed on `market` if given
+pub fn take_offer_accounts(
+    taker: &Pubkey,
+    maker: &Pubkey,
+    pair: &Pair,
+    offer: &Pubkey,
+    treasury: &Pubkey,
+    mode: OfferMode,
+    market: Option<Pubkey>,
+) -> Vec<AccountMeta> {
+    approve::accounts::TakeOffer {
+        taker: *taker,
+        maker: *maker,
+        token_mint_a: pair.mint_a,
+        token_mint_b: pair.mint_b,
+        maker_token_account_a: pair.token_account_a(maker),
+        taker_token_account_a: pair.token_account_a(taker),
+        taker_token_account_b: Some(pair.token_account_b(taker)),
+        maker_token_account_b: Some(pair.token_account_b(maker)),
+        offer: *offer,
+        vault: (mode == OfferMode::Vault).then(|| vault_address(offer)),
+        config: config_address(),
+        treasury: *treasury,
+        treasury_token_account_b: Some(pair.token_account_b(treasury)),
+        market,
+        associated_token_program: associated_token::ID,
+        token_program_a: pair.token_program_a,
+        token_program_b: pair.token_program_b,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None)
+}
+
+/// Builds a `take_offer` of an offer of the pair, listed on `market` if given
+#[allow(clippy::too_many_arguments)]
+pub fn take_offer_ix(
+    taker: &Pubkey,
+    maker: &Pubkey,
+    pair: &Pair,
+    offer: &Pubkey,
+    treasury: &Pubkey,
+    expected_token_a_amount: u64,
+    max_token_b_amount: u64,
+    mode: OfferMode,
+    market: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: take_offer_accounts(taker, maker, pair, offer, treasury, mode, market),
+        data: approve::instruction::TakeOffer {
+            expected_token_a_amount,
+            max_token_b_amount,
+            allowlist_proof: Vec::new(),
+        }
+        .data(),
+    }
+}
+
+/// Builds a `take_offer_partial` of an unlisted delegation mode offer paying
+/// `token_b_amount`, see [`take_offer_ix`]
+pub fn take_offer_partial_ix(
+    taker: &Pubkey,
+    maker: &Pubkey,
+    pair: &Pair,
+    offer: &Pubkey,
+    treasury: &Pubkey,
+    token_b_amount: u64,
+    expected_token_a_amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: take_offer_accounts(taker, maker, pair, offer, treasury, OfferMode::Delegation, None),
+        data: approve::instruction::TakeOfferPartial {
+            token_b_amount,
+            expected_token_a_amount,
+            allowlist_proof: Vec::new(),
+        }
+        .data(),
+    }
+}
+
+/// Builds a `cancel_offer` of an unlisted offer of the pair
+pub fn cancel_offer_ix(maker: &Pubkey, pair: &Pair, offer: &Pubkey, mode: OfferMode) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::CancelOffer {
+            maker: *maker,
+            token_mint_a: pair.mint_a,
+            maker_token_account_a: pair.token_account_a(maker),
+            offer: *offer,
+            vault: (mode == OfferMode::Vault).then(|| vault_address(offer)),
+            market: None,
+            token_program: pair.token_program_a,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::CancelOffer {}.data(),
+    }
+}
+
+/// Builds a `close_expired_offer` of an unlisted offer of the pair, signed by
+/// `closer`
+pub fn close_expired_offer_ix(
+    closer: &Pubkey,
+    maker: &Pubkey,
+    pair: &Pair,
+    offer: &Pubkey,
+    mode: OfferMode,
+) -> Instruction {
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::CloseExpiredOffer {
+            closer: *closer,
+            maker: *maker,
+            token_mint_a: pair.mint_a,
+            maker_token_account_a: pair.token_account_a(maker),
+            offer: *offer,
+            vault: (mode == OfferMode::Vault).then(|| vault_address(offer)),
+            market: None,
+            token_program: pair.token_program_a,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::CloseExpiredOffer {}.data(),
+    }
+}
+
+pub fn market_address(mint_a: &Pubkey, mint_b: &Pubkey) -> Pubkey {
+    let (base_mint, quote_mint) = approve::Market::canonical_pair(*mint_a, *mint_b);
+    Pubkey::find_program_address(&[b"market", base_mint.as_ref(), quote_mint.as_ref()], &approve::ID).0
+}
+
+/// Builds a `create_market` of the pair, the mints are sorted into base and quote
+pub fn create_market_ix(payer: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey) -> Instruction {
+    let (base_mint, quote_mint) = approve::Market::canonical_pair(*mint_a, *mint_b);
+    Instruction {
+        program_id: approve::ID,
+        accounts: approve::accounts::CreateMarket {
+            payer: *payer,
+            base_mint,
+            quote_mint,
+            market: market_address(mint_a, mint_b),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: approve::instruction::CreateMarket {}.data(),
+    }
+}
+
+/// An initialized program with a maker holding token A and a taker holding token B
+pub struct Escrow {
+    pub runtime: TestRuntime,
+    pub treasury: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_program_a: Pubkey,
+    pub token_program_b: Pubkey,
+}
+
+impl Escrow {
+    /// An escrow of two spl-token mints
+    pub fn new(fee_basis_points: u16, maker_balance_a: u64, taker_balance_b: u64) -> Self {
+        let mut runtime = TestRuntime::new();
+        let mint_a = create_mint(&mut runtime, 6);
+        let mint_b = create_mint(&mut runtime, 9);
+        Self::with_mints(runtime, mint_a, mint_b, fee_basis_points, maker_balance_a, taker_balance_b)
+    }
+
+    /// An escrow of mints already created on the runtime
+    pub fn with_mints(
+        mut runtime: TestRuntime,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        fee_basis_points: u16,
+        maker_balance_a: u64,
+        taker_balance_b: u64,
+    ) -> Self {
+        let admin = runtime.create_wallet(10);
+        let treasury = runtime.create_wallet(1);
+        let maker = runtime.create_wallet(10);
+        let taker = runtime.create_wallet(10);
+        create_token_account(&mut runtime, &maker, &mint_a, maker_balance_a);
+        create_token_account(&mut runtime, &taker, &mint_b, taker_balance_b);
+        let token_program_a = runtime.account(&mint_a).expect("mint A").owner;
+        let token_program_b = runtime.account(&mint_b).expect("mint B").owner;
+
+        create_config(&mut runtime, &admin, fee_basis_points, &treasury);
+
+        Self {
+            runtime,
+            treasury,
+            maker,
+            taker,
+            mint_a,
+            mint_b,
+            token_program_a,
+            token_program_b,
+        }
+    }
+
+    pub fn pair(&self) -> Pair {
+        Pair {
+            mint_a: self.mint_a,
+            mint_b: self.mint_b,
+            token_program_a: self.token_program_a,
+            token_program_b: self.token_program_b,
+        }
+    }
+
+    /// Makes a delegation mode offer of token A for token B, returns its address
+    pub fn make_offer(&mut self, token_a_amount: u64, token_b_amount: u64) -> Result<Pubkey, ProgramError> {
+        self.make_listed_offer(token_a_amount, token_b_amount, OfferMode::Delegation, None)
+    }
+
+    /// Makes an offer in `mode` listed on `market`, see [`Escrow::make_offer`]
+    pub fn make_listed_offer(
+        &mut self,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        mode: OfferMode,
+        market: Option<Pubkey>,
+    ) -> Result<Pubkey, ProgramError> {
+        let id = next_offer_id(&self.runtime, &self.maker);
+        let expires_at = self.runtime.clock().unix_timestamp + OFFER_DURATION;
+        self.runtime.process(&make_offer_ix(
+            &self.maker,
+            &self.pair(),
+            id,
+            token_a_amount,
+            token_b_amount,
+            expires_at,
+            mode,
+            market,
+        ))?;
+        Ok(offer_address(&self.maker, id))
+    }
+
+    /// Takes the whole offer as the taker, with slippage limits matching its
+    /// terms less the transfer fee of token A
+    pub fn take_offer(&mut self, offer: &Pubkey) -> Result<(), ProgramError> {
+        let (expected_a, max_b, mode, market) = self
+            .runtime
+            .anchor_account::<approve::Offer>(offer)
+            .map_or((0, u64::MAX, OfferMode::Delegation, None), |offer| {
+                let fee = transfer_fee(&self.runtime, &self.mint_a, offer.remaining_a);
+                (offer.remaining_a - fee, offer.remaining_b, offer.mode, offer.market)
+            });
+        self.runtime.process(&take_offer_ix(
+            &self.taker,
+            &self.maker,
+            &self.pair(),
+            offer,
+            &self.treasury,
+            expected_a,
+            max_b,
+            mode,
+            market,
+        ))
+    }
+
+    /// Cancels the unlisted offer as its maker
+    pub fn cancel_offer(&mut self, offer: &Pubkey) -> Result<(), ProgramError> {
+        let mode = self
+            .runtime
+            .anchor_account::<approve::Offer>(offer)
+            .map_or(OfferMode::Delegation, |offer| offer.mode);
+        self.runtime.process(&cancel_offer_ix(&self.maker, &self.pair(), offer, mode))
+    }
+
+    pub fn balance_a(&self, owner: &Pubkey) -> u64 {
+        token_balance(&self.runtime, &self.pair().token_account_a(owner))
+    }
+
+    pub fn balance_b(&self, owner: &Pubkey) -> u64 {
+        token_balance(&self.runtime, &self.pair().token_account_b(owner))
+    }
+}
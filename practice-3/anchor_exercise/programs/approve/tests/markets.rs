@@ -0,0 +1,83 @@
+//! Listing offers on the order book of a market
+
+mod common;
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use approve::{error::ErrorCode, Market, Offer, OfferMode, MAX_ORDERS_PER_SIDE};
+
+use common::*;
+
+const OFFERED_A: u64 = 10;
+const WANTED_B: u64 = 100;
+
+/// An escrow with the market of its pair
+fn escrow_with_market(maker_balance_a: u64) -> (Escrow, Pubkey) {
+    let mut escrow = Escrow::new(0, maker_balance_a, 1_000);
+    let payer = escrow.runtime.create_wallet(1);
+    escrow
+        .runtime
+        .process(&create_market_ix(&payer, &escrow.mint_a, &escrow.mint_b))
+        .unwrap();
+    let market = market_address(&escrow.mint_a, &escrow.mint_b);
+    (escrow, market)
+}
+
+/// The side of the book the offers of the escrow are listed on
+fn offer_side(escrow: &Escrow, market: &Market) -> Vec<Pubkey> {
+    let side = if market.base_mint == escrow.mint_a {
+        &market.asks
+    } else {
+        &market.bids
+    };
+    side.iter().map(|entry| entry.offer).collect()
+}
+
+#[test]
+fn a_better_offer_evicts_the_worst_order_of_a_full_side() {
+    let (mut escrow, market) = escrow_with_market(1_000);
+    for _ in 0..MAX_ORDERS_PER_SIDE {
+        escrow
+            .make_listed_offer(OFFERED_A, WANTED_B, OfferMode::Vault, Some(market))
+            .unwrap();
+    }
+    let worst = offer_address(&escrow.maker, MAX_ORDERS_PER_SIDE as u64 - 1);
+
+    let worse = escrow.make_listed_offer(OFFERED_A, 2 * WANTED_B, OfferMode::Vault, Some(market));
+    let better = escrow
+        .make_listed_offer(OFFERED_A, WANTED_B / 2, OfferMode::Vault, Some(market))
+        .unwrap();
+
+    assert_eq!(worse, Err(anchor_error(ErrorCode::MarketFull)));
+    let book: Market = escrow.runtime.anchor_account(&market).unwrap();
+    let side = offer_side(&escrow, &book);
+    assert_eq!(side.len(), MAX_ORDERS_PER_SIDE);
+    assert_eq!(side[0], better);
+    assert!(!side.contains(&worst));
+
+    // The evicted offer stays open on its market with its vault and can still be taken
+    let evicted: Offer = escrow.runtime.anchor_account(&worst).unwrap();
+    assert_eq!(evicted.market, Some(market));
+    assert_eq!(token_balance(&escrow.runtime, &vault_address(&worst)), OFFERED_A);
+    escrow.take_offer(&worst).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+    assert!(escrow.runtime.anchor_account::<Offer>(&worst).is_none());
+    assert!(escrow.runtime.account(&vault_address(&worst)).is_none());
+    let book: Market = escrow.runtime.anchor_account(&market).unwrap();
+    assert_eq!(offer_side(&escrow, &book).len(), MAX_ORDERS_PER_SIDE);
+}
+
+#[test]
+fn listed_delegation_offer_is_rejected() {
+    let (mut escrow, market) = escrow_with_market(1_000);
+
+    // One approve per token account backs only the latest delegation offer, so
+    // a single wallet could fill the book with offers nobody can take
+    let listed = escrow.make_listed_offer(OFFERED_A, WANTED_B, OfferMode::Delegation, Some(market));
+    let unlisted = escrow.make_offer(OFFERED_A, WANTED_B);
+
+    assert_eq!(listed, Err(anchor_error(ErrorCode::MarketRequiresVault)));
+    assert!(unlisted.is_ok());
+    let book: Market = escrow.runtime.anchor_account(&market).unwrap();
+    assert!(offer_side(&escrow, &book).is_empty());
+}
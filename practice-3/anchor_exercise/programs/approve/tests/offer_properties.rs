@@ -0,0 +1,416 @@
+//! Property tests of offer settlement over random sequences of operations
+//!
+//! Every sequence runs with fresh wallets and mints, random decimals, fees and
+//! balances. After each operation the tests check that no tokens are created
+//! or destroyed, that an offer never pulls more token A than the maker
+//! delegated to it, and that a closed offer can not be taken.
+
+mod common;
+
+use std::cell::RefCell;
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_spl::token::spl_token;
+use approve::{Offer, OfferMode, BASIS_POINTS_DENOMINATOR, MAX_FEE_BASIS_POINTS};
+use proptest::{prelude::*, sample::Index, test_runner::TestRunner};
+
+use common::*;
+
+const MAKERS: usize = 2;
+const TAKERS: usize = 2;
+const MAX_AMOUNT: u64 = 1_000_000_000_000;
+const MAX_BALANCE: u64 = 5 * MAX_AMOUNT;
+
+#[derive(Clone, Debug)]
+enum Operation {
+    Make {
+        maker: usize,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    },
+    Take {
+        offer: Index,
+        taker: usize,
+    },
+    TakePartial {
+        offer: Index,
+        taker: usize,
+        token_b_amount: Index,
+    },
+    Cancel {
+        offer: Index,
+    },
+    /// The maker revokes the delegation of its token A account
+    Revoke {
+        maker: usize,
+    },
+    /// The maker sends token A to the other maker
+    Spend {
+        maker: usize,
+        amount: u64,
+    },
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        3 => (0..MAKERS, 1..=MAX_AMOUNT, 1..=MAX_AMOUNT).prop_map(
+            |(maker, token_a_amount, token_b_amount)| Operation::Make {
+                maker,
+                token_a_amount,
+                token_b_amount,
+            }
+        ),
+        3 => (any::<Index>(), 0..TAKERS).prop_map(|(offer, taker)| Operation::Take { offer, taker }),
+        2 => (any::<Index>(), 0..TAKERS, any::<Index>()).prop_map(|(offer, taker, token_b_amount)| {
+            Operation::TakePartial {
+                offer,
+                taker,
+                token_b_amount,
+            }
+        }),
+        1 => any::<Index>().prop_map(|offer| Operation::Cancel { offer }),
+        1 => (0..MAKERS).prop_map(|maker| Operation::Revoke { maker }),
+        1 => (0..MAKERS, 1..=MAX_AMOUNT).prop_map(|(maker, amount)| Operation::Spend { maker, amount }),
+    ]
+}
+
+/// An offer made during the sequence and the token A it has sent so far
+struct MadeOffer {
+    address: Pubkey,
+    maker: usize,
+    token_a_amount: u64,
+    sent_a: u64,
+}
+
+/// Balances touched by a fill
+#[derive(Debug, PartialEq, Eq)]
+struct FillBalances {
+    maker_a: u64,
+    maker_b: u64,
+    taker_a: u64,
+    taker_b: u64,
+    treasury_b: u64,
+    delegated_a: u64,
+}
+
+struct World<'a> {
+    runtime: &'a mut TestRuntime,
+    fee_basis_points: u16,
+    treasury: Pubkey,
+    makers: Vec<Pubkey>,
+    takers: Vec<Pubkey>,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    supply_a: u64,
+    supply_b: u64,
+    offers: Vec<MadeOffer>,
+}
+
+impl<'a> World<'a> {
+    fn new(
+        runtime: &'a mut TestRuntime,
+        decimals_a: u8,
+        decimals_b: u8,
+        fee_basis_points: u16,
+        maker_balances: &[u64],
+        taker_balances: &[u64],
+    ) -> Self {
+        let admin = runtime.create_wallet(10);
+        let treasury = runtime.create_wallet(1);
+        let mint_a = create_mint(runtime, decimals_a);
+        let mint_b = create_mint(runtime, decimals_b);
+
+        let makers = maker_balances
+            .iter()
+            .map(|balance| {
+                let maker = runtime.create_wallet(10);
+                create_token_account(runtime, &maker, &mint_a, *balance);
+                maker
+            })
+            .collect();
+        let takers = taker_balances
+            .iter()
+            .map(|balance| {
+                let taker = runtime.create_wallet(10);
+                create_token_account(runtime, &taker, &mint_b, *balance);
+                taker
+            })
+            .collect();
+        create_config(runtime, &admin, fee_basis_points, &treasury);
+
+        Self {
+            runtime,
+            fee_basis_points,
+            treasury,
+            makers,
+            takers,
+            mint_a,
+            mint_b,
+            supply_a: maker_balances.iter().sum(),
+            supply_b: taker_balances.iter().sum(),
+            offers: Vec::new(),
+        }
+    }
+
+    fn pair(&self) -> Pair {
+        Pair::new(self.mint_a, self.mint_b)
+    }
+
+    fn offer_state(&self, offer: usize) -> Option<Offer> {
+        self.runtime.anchor_account(&self.offers[offer].address)
+    }
+
+    fn fill_balances(&self, offer: usize, taker: usize) -> FillBalances {
+        let maker = &self.makers[self.offers[offer].maker];
+        let taker = &self.takers[taker];
+        let balance = |owner: &Pubkey, mint: &Pubkey| {
+            token_balance(self.runtime, &token_account_address(owner, mint))
+        };
+        let maker_account_a = token_account(self.runtime, &token_account_address(maker, &self.mint_a))
+            .expect("maker token A account");
+        FillBalances {
+            maker_a: maker_account_a.amount,
+            maker_b: balance(maker, &self.mint_b),
+            taker_a: balance(taker, &self.mint_a),
+            taker_b: balance(taker, &self.mint_b),
+            treasury_b: balance(&self.treasury, &self.mint_b),
+            delegated_a: maker_account_a.delegated_amount,
+        }
+    }
+
+    /// Whether the maker's token A account still lets the offer pull what is left of it
+    fn can_pull_remaining_a(&self, offer: usize, state: &Offer) -> bool {
+        let maker = &self.makers[self.offers[offer].maker];
+        let maker_account_a = token_account(self.runtime, &token_account_address(maker, &self.mint_a))
+            .expect("maker token A account");
+        maker_account_a.delegate == Some(self.offers[offer].address).into()
+            && maker_account_a.delegated_amount >= state.remaining_a
+            && maker_account_a.amount >= state.remaining_a
+    }
+
+    fn apply(&mut self, operation: &Operation) -> Result<(), TestCaseError> {
+        match *operation {
+            Operation::Make {
+                maker,
+                token_a_amount,
+                token_b_amount,
+            } => {
+                let maker_key = self.makers[maker];
+                let id = next_offer_id(self.runtime, &maker_key);
+                let expires_at = self.runtime.clock().unix_timestamp + OFFER_DURATION;
+                let make = make_offer_ix(
+                    &maker_key,
+                    &self.pair(),
+                    id,
+                    token_a_amount,
+                    token_b_amount,
+                    expires_at,
+                    OfferMode::Delegation,
+                    None,
+                );
+                if self.runtime.process(&make).is_ok() {
+                    self.offers.push(MadeOffer {
+                        address: offer_address(&maker_key, id),
+                        maker,
+                        token_a_amount,
+                        sent_a: 0,
+                    });
+                }
+            }
+            Operation::Take { offer, taker } => {
+                if self.offers.is_empty() {
+                    return Ok(());
+                }
+                let offer = offer.index(self.offers.len());
+                let state = self.offer_state(offer);
+                let (expected_a, max_b) = state
+                    .as_ref()
+                    .map_or((0, u64::MAX), |state| (state.remaining_a, state.remaining_b));
+                let take = take_offer_ix(
+                    &self.takers[taker],
+                    &self.makers[self.offers[offer].maker],
+                    &self.pair(),
+                    &self.offers[offer].address,
+                    &self.treasury,
+                    expected_a,
+                    max_b,
+                    OfferMode::Delegation,
+                    None,
+                );
+                let live = state.as_ref().is_some_and(|state| {
+                    self.can_pull_remaining_a(offer, state)
+                        && self.fill_balances(offer, taker).taker_b >= state.remaining_b
+                });
+                let result = self.fill(offer, taker, state.as_ref(), &take)?;
+                if live {
+                    prop_assert!(result, "a live offer could not be taken");
+                }
+            }
+            Operation::TakePartial {
+                offer,
+                taker,
+                token_b_amount,
+            } => {
+                if self.offers.is_empty() {
+                    return Ok(());
+                }
+                let offer = offer.index(self.offers.len());
+                let state = self.offer_state(offer);
+                let remaining_b = state.as_ref().map_or(1, |state| state.remaining_b);
+                let token_b_amount = 1 + token_b_amount.index(remaining_b as usize) as u64;
+                let take = take_offer_partial_ix(
+                    &self.takers[taker],
+                    &self.makers[self.offers[offer].maker],
+                    &self.pair(),
+                    &self.offers[offer].address,
+                    &self.treasury,
+                    token_b_amount,
+                    0,
+                );
+                self.fill(offer, taker, state.as_ref(), &take)?;
+            }
+            Operation::Cancel { offer } => {
+                if self.offers.is_empty() {
+                    return Ok(());
+                }
+                let offer = offer.index(self.offers.len());
+                let was_open = self.offer_state(offer).is_some();
+                let cancel = cancel_offer_ix(
+                    &self.makers[self.offers[offer].maker],
+                    &self.pair(),
+                    &self.offers[offer].address,
+                    OfferMode::Delegation,
+                );
+                let cancelled = self.runtime.process(&cancel).is_ok();
+                prop_assert_eq!(cancelled, was_open);
+                prop_assert!(self.offer_state(offer).is_none());
+            }
+            Operation::Revoke { maker } => {
+                let maker = self.makers[maker];
+                let revoke = spl_token::instruction::revoke(
+                    &spl_token::ID,
+                    &token_account_address(&maker, &self.mint_a),
+                    &maker,
+                    &[],
+                )
+                .expect("revoke instruction");
+                prop_assert!(self.runtime.process(&revoke).is_ok());
+            }
+            Operation::Spend { maker, amount } => {
+                let from = self.makers[maker];
+                let to = self.makers[(maker + 1) % MAKERS];
+                let transfer = spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    &token_account_address(&from, &self.mint_a),
+                    &token_account_address(&to, &self.mint_a),
+                    &from,
+                    &[],
+                    amount,
+                )
+                .expect("transfer instruction");
+                let balance = token_balance(self.runtime, &token_account_address(&from, &self.mint_a));
+                prop_assert_eq!(self.runtime.process(&transfer).is_ok(), amount <= balance);
+            }
+        }
+        self.check_conservation()
+    }
+
+    /// Runs a take of `offer` and checks the balances it moved, returns
+    /// whether it succeeded
+    fn fill(
+        &mut self,
+        offer: usize,
+        taker: usize,
+        state: Option<&Offer>,
+        take: &anchor_lang::solana_program::instruction::Instruction,
+    ) -> Result<bool, TestCaseError> {
+        let before = self.fill_balances(offer, taker);
+        let result = self.runtime.process(take);
+        let after = self.fill_balances(offer, taker);
+
+        let Some(state) = state else {
+            prop_assert!(result.is_err(), "a closed offer was taken");
+            prop_assert_eq!(before, after);
+            return Ok(false);
+        };
+        if result.is_err() {
+            prop_assert_eq!(before, after);
+            return Ok(false);
+        }
+
+        let filled_a = before.maker_a - after.maker_a;
+        let filled_b = before.taker_b - after.taker_b;
+        let fee = after.treasury_b - before.treasury_b;
+        prop_assert_eq!(after.taker_a - before.taker_a, filled_a);
+        prop_assert_eq!(after.maker_b - before.maker_b + fee, filled_b);
+        prop_assert_eq!(
+            fee as u128,
+            (filled_b as u128 * self.fee_basis_points as u128)
+                .div_ceil(BASIS_POINTS_DENOMINATOR as u128)
+        );
+        prop_assert_eq!(before.delegated_a - after.delegated_a, filled_a);
+        // Rounding never gives the taker a better price than the offer
+        prop_assert!(
+            filled_a as u128 * state.remaining_b as u128
+                <= filled_b as u128 * state.remaining_a as u128
+        );
+
+        let made = &mut self.offers[offer];
+        made.sent_a += filled_a;
+        prop_assert!(made.sent_a <= made.token_a_amount, "the offer pulled more than delegated");
+        match self.offer_state(offer) {
+            Some(state) => {
+                prop_assert_eq!(state.remaining_a, self.offers[offer].token_a_amount - self.offers[offer].sent_a)
+            }
+            None => prop_assert_eq!(self.offers[offer].sent_a, self.offers[offer].token_a_amount),
+        }
+        Ok(true)
+    }
+
+    fn check_conservation(&self) -> Result<(), TestCaseError> {
+        let owners: Vec<Pubkey> = [self.treasury]
+            .into_iter()
+            .chain(self.makers.iter().copied())
+            .chain(self.takers.iter().copied())
+            .collect();
+        prop_assert_eq!(token_supply(self.runtime, &self.mint_a, &owners), self.supply_a);
+        prop_assert_eq!(token_supply(self.runtime, &self.mint_b, &owners), self.supply_b);
+        Ok(())
+    }
+}
+
+#[test]
+fn offer_settlement_preserves_invariants() {
+    // Every case shares one bank, with its own wallets and mints, since a
+    // program-test bank keeps its threads and memory after it is dropped
+    let runtime = RefCell::new(TestRuntime::new());
+    let strategy = (
+        0u8..=9,
+        0u8..=9,
+        0..=MAX_FEE_BASIS_POINTS,
+        prop::array::uniform2(0..=MAX_BALANCE),
+        prop::array::uniform2(0..=MAX_BALANCE),
+        prop::collection::vec(operation(), 1..24),
+    );
+    let result = TestRunner::new(ProptestConfig::with_cases(128)).run(
+        &strategy,
+        |(decimals_a, decimals_b, fee_basis_points, maker_balances, taker_balances, operations)| {
+            let mut runtime = runtime.borrow_mut();
+            let mut world = World::new(
+                &mut runtime,
+                decimals_a,
+                decimals_b,
+                fee_basis_points,
+                &maker_balances,
+                &taker_balances,
+            );
+            for operation in &operations {
+                world.apply(operation)?;
+            }
+            Ok(())
+        },
+    );
+    if let Err(error) = result {
+        panic!("{error}");
+    }
+}
@@ -0,0 +1,341 @@
+//! `make_offer` and `take_offer` against the program on a program-test bank
+
+mod common;
+
+use anchor_lang::{error::ErrorCode as AnchorErrorCode, solana_program::program_option::COption};
+use anchor_spl::token::spl_token;
+use approve::{error::ErrorCode, Offer, OfferMode};
+
+use common::*;
+
+const OFFERED_A: u64 = 10_000_000;
+const WANTED_B: u64 = 100_000_000;
+
+fn escrow() -> Escrow {
+    Escrow::new(0, 100_000_000, 300_000_000)
+}
+
+#[test]
+fn make_offer_delegates_offered_tokens_to_the_offer() {
+    let mut escrow = escrow();
+
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    let offer: Offer = escrow.runtime.anchor_account(&offer_address).unwrap();
+    assert_eq!(offer.id, 0);
+    assert_eq!(offer.maker, escrow.maker);
+    assert_eq!(offer.token_mint_a, escrow.mint_a);
+    assert_eq!(offer.token_mint_b, escrow.mint_b);
+    assert_eq!(offer.token_a_amount, OFFERED_A);
+    assert_eq!(offer.token_b_wanted_amount, WANTED_B);
+    assert_eq!(offer.mode, OfferMode::Delegation);
+
+    // Tokens stay with the maker, the offer only gets an allowance
+    let maker_account_a = token_account_address(&escrow.maker, &escrow.mint_a);
+    let maker_account_a = token_account(&escrow.runtime, &maker_account_a).unwrap();
+    assert_eq!(maker_account_a.amount, 100_000_000);
+    assert_eq!(maker_account_a.delegate, COption::Some(offer_address));
+    assert_eq!(maker_account_a.delegated_amount, OFFERED_A);
+    assert_eq!(next_offer_id(&escrow.runtime, &escrow.maker), 1);
+}
+
+#[test]
+fn take_offer_swaps_tokens_and_closes_the_offer() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    escrow.take_offer(&offer_address).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.maker), 90_000_000);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+    assert_eq!(escrow.balance_b(&escrow.taker), 200_000_000);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_none());
+    assert_eq!(escrow.runtime.lamports(&offer_address), 0);
+
+    let maker_account_a = token_account_address(&escrow.maker, &escrow.mint_a);
+    let maker_account_a = token_account(&escrow.runtime, &maker_account_a).unwrap();
+    assert_eq!(maker_account_a.delegated_amount, 0);
+}
+
+#[test]
+fn take_offer_fails_after_the_maker_revokes_the_delegation() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let maker_account_a = token_account_address(&escrow.maker, &escrow.mint_a);
+    let revoke = spl_token::instruction::revoke(&spl_token::ID, &maker_account_a, &escrow.maker, &[]).unwrap();
+    escrow.runtime.process(&revoke).unwrap();
+
+    let result = escrow.take_offer(&offer_address);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::DelegateRevoked)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_some());
+}
+
+#[test]
+fn take_offer_fails_with_another_maker() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let impostor = escrow.runtime.create_wallet(1);
+    create_token_account(&mut escrow.runtime, &impostor, &escrow.mint_a, 0);
+
+    let take = take_offer_ix(
+        &escrow.taker,
+        &impostor,
+        &escrow.pair(),
+        &offer_address,
+        &escrow.treasury,
+        OFFERED_A,
+        WANTED_B,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&take);
+
+    // The offer address is derived from the maker, so the seeds check rejects it
+    assert_eq!(result, Err(anchor_error(AnchorErrorCode::ConstraintSeeds)));
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+    assert_eq!(escrow.balance_b(&impostor), 0);
+}
+
+#[test]
+fn take_offer_fails_with_another_wanted_mint() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let other_mint = create_mint(&mut escrow.runtime, 9);
+    create_token_account(&mut escrow.runtime, &escrow.taker, &other_mint, WANTED_B);
+
+    let take = take_offer_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &Pair {
+            mint_b: other_mint,
+            ..escrow.pair()
+        },
+        &offer_address,
+        &escrow.treasury,
+        OFFERED_A,
+        WANTED_B,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&take);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::OfferMintMismatch)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+    assert_eq!(
+        token_balance(&escrow.runtime, &token_account_address(&escrow.taker, &other_mint)),
+        WANTED_B
+    );
+}
+
+#[test]
+fn take_offer_fails_for_an_offer_that_was_already_taken() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    escrow.take_offer(&offer_address).unwrap();
+
+    let result = escrow.take_offer(&offer_address);
+
+    assert_eq!(result, Err(anchor_error(AnchorErrorCode::AccountNotInitialized)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 90_000_000);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+    assert_eq!(escrow.balance_b(&escrow.taker), 200_000_000);
+}
+
+#[test]
+fn consecutive_offers_get_consecutive_ids() {
+    let mut escrow = escrow();
+
+    let first = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let second = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    assert_eq!(first, offer_address(&escrow.maker, 0));
+    assert_eq!(second, offer_address(&escrow.maker, 1));
+    assert_eq!(escrow.runtime.anchor_account::<Offer>(&second).unwrap().id, 1);
+}
+
+#[test]
+fn make_offer_fails_for_a_zero_amount() {
+    let mut escrow = escrow();
+
+    let no_token_a = escrow.make_offer(0, WANTED_B);
+    let no_token_b = escrow.make_offer(OFFERED_A, 0);
+
+    assert_eq!(no_token_a, Err(anchor_error(ErrorCode::ZeroAmount)));
+    assert_eq!(no_token_b, Err(anchor_error(ErrorCode::ZeroAmount)));
+    assert_eq!(next_offer_id(&escrow.runtime, &escrow.maker), 0);
+}
+
+#[test]
+fn make_offer_fails_for_identical_mints() {
+    let mut escrow = escrow();
+    let expires_at = escrow.runtime.clock().unix_timestamp + OFFER_DURATION;
+
+    let make = make_offer_ix(
+        &escrow.maker,
+        &Pair {
+            mint_b: escrow.mint_a,
+            ..escrow.pair()
+        },
+        0,
+        OFFERED_A,
+        WANTED_B,
+        expires_at,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&make);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::IdenticalMints)));
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address(&escrow.maker, 0)).is_none());
+}
+
+#[test]
+fn make_offer_fails_for_an_expiry_in_the_past() {
+    let mut escrow = escrow();
+    let now = escrow.runtime.clock().unix_timestamp;
+
+    let make = make_offer_ix(
+        &escrow.maker,
+        &escrow.pair(),
+        0,
+        OFFERED_A,
+        WANTED_B,
+        now - 1,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&make);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::InvalidExpiry)));
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address(&escrow.maker, 0)).is_none());
+}
+
+#[test]
+fn take_offer_fails_for_the_maker_of_the_offer() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    create_token_account(&mut escrow.runtime, &escrow.maker, &escrow.mint_b, WANTED_B);
+
+    let take = take_offer_ix(
+        &escrow.maker,
+        &escrow.maker,
+        &escrow.pair(),
+        &offer_address,
+        &escrow.treasury,
+        OFFERED_A,
+        WANTED_B,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&take);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::SelfTake)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_some());
+}
+
+#[test]
+fn take_offer_partial_fails_for_a_fill_outside_the_remaining_amount() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    for token_b_amount in [0, WANTED_B + 1] {
+        let take = take_offer_partial_ix(
+            &escrow.taker,
+            &escrow.maker,
+            &escrow.pair(),
+            &offer_address,
+            &escrow.treasury,
+            token_b_amount,
+            0,
+        );
+        let result = escrow.runtime.process(&take);
+
+        assert_eq!(result, Err(anchor_error(ErrorCode::InvalidFillAmount)));
+    }
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+}
+
+#[test]
+fn take_offer_partial_fails_for_a_fill_worth_no_token_a() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    // One unit of token B is worth a tenth of a unit of token A
+    let take = take_offer_partial_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &escrow.pair(),
+        &offer_address,
+        &escrow.treasury,
+        1,
+        0,
+    );
+    let result = escrow.runtime.process(&take);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::FillTooSmall)));
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+}
+
+#[test]
+fn take_offer_fails_after_the_offer_expires() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let expires_at = escrow.runtime.anchor_account::<Offer>(&offer_address).unwrap().expires_at;
+    escrow.runtime.set_unix_timestamp(expires_at);
+
+    let result = escrow.take_offer(&offer_address);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::OfferExpired)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+}
+
+#[test]
+fn close_expired_offer_fails_before_the_offer_expires() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    let close = close_expired_offer_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &escrow.pair(),
+        &offer_address,
+        OfferMode::Delegation,
+    );
+    let result = escrow.runtime.process(&close);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::OfferNotExpired)));
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_some());
+}
+
+#[test]
+fn take_offer_fails_when_the_maker_lowers_the_allowance() {
+    let mut escrow = escrow();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let maker_account_a = token_account_address(&escrow.maker, &escrow.mint_a);
+    let approve = spl_token::instruction::approve(
+        &spl_token::ID,
+        &maker_account_a,
+        &offer_address,
+        &escrow.maker,
+        &[],
+        OFFERED_A - 1,
+    )
+    .unwrap();
+    escrow.runtime.process(&approve).unwrap();
+
+    let result = escrow.take_offer(&offer_address);
+
+    assert_eq!(result, Err(anchor_error(ErrorCode::InsufficientDelegatedAmount)));
+    assert_eq!(escrow.balance_a(&escrow.maker), 100_000_000);
+    assert_eq!(escrow.balance_b(&escrow.taker), 300_000_000);
+}
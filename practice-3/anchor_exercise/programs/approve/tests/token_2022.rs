@@ -0,0 +1,173 @@
+//! Offers of Token-2022 mints with extensions
+
+mod common;
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use approve::{error::ErrorCode, Offer, OfferMode};
+
+use common::*;
+
+const OFFERED_A: u64 = 1_000_000;
+const WANTED_B: u64 = 2_000_000;
+const MAKER_BALANCE_A: u64 = 10 * OFFERED_A;
+const TAKER_BALANCE_B: u64 = 10 * WANTED_B;
+
+const TRANSFER_FEE: MintExtension = MintExtension::TransferFee {
+    basis_points: 100,
+    maximum_fee: u64::MAX,
+};
+
+/// An escrow offering a Token-2022 mint with the extensions for an spl-token mint
+fn escrow_offering(extensions: &[MintExtension]) -> Escrow {
+    let mut runtime = TestRuntime::new();
+    let mint_a = create_token_2022_mint(&mut runtime, 6, extensions);
+    let mint_b = create_mint(&mut runtime, 9);
+    Escrow::with_mints(runtime, mint_a, mint_b, 0, MAKER_BALANCE_A, TAKER_BALANCE_B)
+}
+
+/// An escrow with a protocol fee wanting a Token-2022 mint with the extensions
+fn escrow_wanting(extensions: &[MintExtension], fee_basis_points: u16) -> Escrow {
+    let mut runtime = TestRuntime::new();
+    let mint_a = create_mint(&mut runtime, 6);
+    let mint_b = create_token_2022_mint(&mut runtime, 9, extensions);
+    Escrow::with_mints(runtime, mint_a, mint_b, fee_basis_points, MAKER_BALANCE_A, TAKER_BALANCE_B)
+}
+
+/// Makes a vault offer and returns it with the transfer fee the maker paid on
+/// the deposit
+fn make_vault_offer(escrow: &mut Escrow) -> (Pubkey, u64) {
+    let offer_address = escrow
+        .make_listed_offer(OFFERED_A, WANTED_B, OfferMode::Vault, None)
+        .unwrap();
+    assert_eq!(token_balance(&escrow.runtime, &vault_address(&offer_address)), OFFERED_A);
+    let deposit_fee = MAKER_BALANCE_A - escrow.balance_a(&escrow.maker) - OFFERED_A;
+    assert!(deposit_fee > 0);
+    (offer_address, deposit_fee)
+}
+
+#[test]
+fn vault_offer_of_a_transfer_fee_mint_closes_its_vault_when_taken() {
+    let mut escrow = escrow_offering(&[TRANSFER_FEE]);
+    let (offer_address, deposit_fee) = make_vault_offer(&mut escrow);
+
+    escrow.take_offer(&offer_address).unwrap();
+
+    let withdrawal_fee = transfer_fee(&escrow.runtime, &escrow.mint_a, OFFERED_A);
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A - withdrawal_fee);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_none());
+    assert!(escrow.runtime.account(&vault_address(&offer_address)).is_none());
+    // The fee withheld in the vault by the deposit was harvested to the mint
+    assert_eq!(withheld_mint_fees(&escrow.runtime, &escrow.mint_a), deposit_fee);
+}
+
+#[test]
+fn vault_offer_of_a_transfer_fee_mint_closes_its_vault_when_cancelled() {
+    let mut escrow = escrow_offering(&[TRANSFER_FEE]);
+    let (offer_address, deposit_fee) = make_vault_offer(&mut escrow);
+
+    escrow.cancel_offer(&offer_address).unwrap();
+
+    let refund_fee = transfer_fee(&escrow.runtime, &escrow.mint_a, OFFERED_A);
+    assert_eq!(
+        escrow.balance_a(&escrow.maker),
+        MAKER_BALANCE_A - deposit_fee - refund_fee
+    );
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_none());
+    assert!(escrow.runtime.account(&vault_address(&offer_address)).is_none());
+    assert_eq!(withheld_mint_fees(&escrow.runtime, &escrow.mint_a), deposit_fee);
+}
+
+#[test]
+fn vault_offer_of_a_transfer_fee_mint_closes_its_vault_when_expired() {
+    let mut escrow = escrow_offering(&[TRANSFER_FEE]);
+    let (offer_address, deposit_fee) = make_vault_offer(&mut escrow);
+    let expires_at = escrow.runtime.anchor_account::<Offer>(&offer_address).unwrap().expires_at;
+    escrow.runtime.set_unix_timestamp(expires_at);
+
+    let close = close_expired_offer_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &escrow.pair(),
+        &offer_address,
+        OfferMode::Vault,
+    );
+    escrow.runtime.process(&close).unwrap();
+
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_none());
+    assert!(escrow.runtime.account(&vault_address(&offer_address)).is_none());
+    assert_eq!(withheld_mint_fees(&escrow.runtime, &escrow.mint_a), deposit_fee);
+}
+
+#[test]
+fn transfer_fee_of_token_a_is_paid_by_the_taker() {
+    let mut escrow = escrow_offering(&[TRANSFER_FEE]);
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+    let fee = transfer_fee(&escrow.runtime, &escrow.mint_a, OFFERED_A);
+
+    // Expecting the full offered amount exceeds the slippage limit
+    let take = take_offer_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &escrow.pair(),
+        &offer_address,
+        &escrow.treasury,
+        OFFERED_A,
+        WANTED_B,
+        OfferMode::Delegation,
+        None,
+    );
+    let result = escrow.runtime.process(&take);
+    assert_eq!(result, Err(anchor_error(ErrorCode::SlippageExceeded)));
+
+    escrow.take_offer(&offer_address).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.maker), MAKER_BALANCE_A - OFFERED_A);
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A - fee);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+}
+
+#[test]
+fn transfer_fee_of_token_b_is_withheld_from_the_maker_and_the_treasury() {
+    let mut escrow = escrow_wanting(&[TRANSFER_FEE], 100);
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    escrow.take_offer(&offer_address).unwrap();
+
+    let protocol_fee = WANTED_B / 100;
+    let maker_share = WANTED_B - protocol_fee;
+    assert_eq!(escrow.balance_b(&escrow.taker), TAKER_BALANCE_B - WANTED_B);
+    assert_eq!(
+        escrow.balance_b(&escrow.maker),
+        maker_share - transfer_fee(&escrow.runtime, &escrow.mint_b, maker_share)
+    );
+    assert_eq!(
+        escrow.balance_b(&escrow.treasury),
+        protocol_fee - transfer_fee(&escrow.runtime, &escrow.mint_b, protocol_fee)
+    );
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+}
+
+#[test]
+fn make_offer_fails_for_a_permanent_delegate_mint() {
+    let mut offering = escrow_offering(&[MintExtension::PermanentDelegate]);
+    let mut wanting = escrow_wanting(&[MintExtension::PermanentDelegate], 0);
+
+    let offered = offering.make_offer(OFFERED_A, WANTED_B);
+    let wanted = wanting.make_offer(OFFERED_A, WANTED_B);
+
+    assert_eq!(offered, Err(anchor_error(ErrorCode::PermanentDelegateMint)));
+    assert_eq!(wanted, Err(anchor_error(ErrorCode::PermanentDelegateMint)));
+}
+
+#[test]
+fn make_offer_fails_for_a_mint_whose_accounts_start_frozen() {
+    let mut offering = escrow_offering(&[MintExtension::DefaultFrozen]);
+    let mut wanting = escrow_wanting(&[MintExtension::DefaultFrozen], 0);
+
+    let offered = offering.make_offer(OFFERED_A, WANTED_B);
+    let wanted = wanting.make_offer(OFFERED_A, WANTED_B);
+
+    assert_eq!(offered, Err(anchor_error(ErrorCode::DefaultFrozenMint)));
+    assert_eq!(wanted, Err(anchor_error(ErrorCode::DefaultFrozenMint)));
+}
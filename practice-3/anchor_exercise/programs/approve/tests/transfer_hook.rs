@@ -0,0 +1,118 @@
+//! Offers of a Token-2022 mint whose transfers run the SPL transfer hook example
+
+mod common;
+
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use approve::{error::ErrorCode, Offer, OfferMode};
+
+use common::*;
+
+const OFFERED_A: u64 = 1_000_000;
+const WANTED_B: u64 = 2_000_000;
+const MAKER_BALANCE_A: u64 = 10 * OFFERED_A;
+
+/// An escrow offering a mint with the transfer hook, with the accounts its
+/// transfers need
+fn escrow_with_hook() -> (Escrow, Vec<AccountMeta>) {
+    let mut runtime = TestRuntime::new();
+    let mint_a = create_token_2022_mint(&mut runtime, 6, &[MintExtension::TransferHook]);
+    let mint_b = create_mint(&mut runtime, 9);
+    let hook_accounts = init_transfer_hook(&mut runtime, &mint_a);
+    let escrow = Escrow::with_mints(runtime, mint_a, mint_b, 0, MAKER_BALANCE_A, 10 * WANTED_B);
+    (escrow, hook_accounts)
+}
+
+fn with_accounts(mut instruction: Instruction, accounts: &[AccountMeta]) -> Instruction {
+    instruction.accounts.extend_from_slice(accounts);
+    instruction
+}
+
+/// Builds a `make_offer` of the escrow's next offer followed by `hook_accounts`
+fn make_offer(escrow: &Escrow, mode: OfferMode, hook_accounts: &[AccountMeta]) -> Instruction {
+    let id = next_offer_id(&escrow.runtime, &escrow.maker);
+    let expires_at = escrow.runtime.clock().unix_timestamp + OFFER_DURATION;
+    let make = make_offer_ix(
+        &escrow.maker,
+        &escrow.pair(),
+        id,
+        OFFERED_A,
+        WANTED_B,
+        expires_at,
+        mode,
+        None,
+    );
+    with_accounts(make, hook_accounts)
+}
+
+/// Builds a `take_offer` of the whole offer followed by `hook_accounts`
+fn take_offer(
+    escrow: &Escrow,
+    offer: &Pubkey,
+    mode: OfferMode,
+    hook_accounts: &[AccountMeta],
+) -> Instruction {
+    let take = take_offer_ix(
+        &escrow.taker,
+        &escrow.maker,
+        &escrow.pair(),
+        offer,
+        &escrow.treasury,
+        OFFERED_A,
+        WANTED_B,
+        mode,
+        None,
+    );
+    with_accounts(take, hook_accounts)
+}
+
+#[test]
+fn take_offer_runs_the_transfer_hook_of_token_a() {
+    let (mut escrow, hook_accounts) = escrow_with_hook();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    let take = take_offer(&escrow, &offer_address, OfferMode::Delegation, &hook_accounts);
+    escrow.runtime.process(&take).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.maker), MAKER_BALANCE_A - OFFERED_A);
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+    assert_eq!(escrow.balance_b(&escrow.maker), WANTED_B);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_none());
+}
+
+#[test]
+fn vault_deposit_and_withdrawal_run_the_transfer_hook_of_token_a() {
+    let (mut escrow, hook_accounts) = escrow_with_hook();
+    let offer_address = offer_address(&escrow.maker, next_offer_id(&escrow.runtime, &escrow.maker));
+
+    let make = make_offer(&escrow, OfferMode::Vault, &hook_accounts);
+    escrow.runtime.process(&make).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.maker), MAKER_BALANCE_A - OFFERED_A);
+    assert_eq!(token_balance(&escrow.runtime, &vault_address(&offer_address)), OFFERED_A);
+
+    let take = take_offer(&escrow, &offer_address, OfferMode::Vault, &hook_accounts);
+    escrow.runtime.process(&take).unwrap();
+
+    assert_eq!(escrow.balance_a(&escrow.taker), OFFERED_A);
+    assert!(escrow.runtime.account(&vault_address(&offer_address)).is_none());
+}
+
+#[test]
+fn transfers_of_token_a_fail_without_the_transfer_hook_accounts() {
+    let (mut escrow, _) = escrow_with_hook();
+    let offer_address = escrow.make_offer(OFFERED_A, WANTED_B).unwrap();
+
+    let take = take_offer(&escrow, &offer_address, OfferMode::Delegation, &[]);
+    let take_result = escrow.runtime.process(&take);
+    let make = make_offer(&escrow, OfferMode::Vault, &[]);
+    let make_result = escrow.runtime.process(&make);
+
+    assert_eq!(take_result, Err(anchor_error(ErrorCode::MissingTransferHookAccounts)));
+    assert_eq!(make_result, Err(anchor_error(ErrorCode::MissingTransferHookAccounts)));
+    assert_eq!(escrow.balance_a(&escrow.maker), MAKER_BALANCE_A);
+    assert_eq!(escrow.balance_b(&escrow.taker), 10 * WANTED_B);
+    assert!(escrow.runtime.anchor_account::<Offer>(&offer_address).is_some());
+}
@@ -3,6 +3,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { type Program, BN } from "@coral-xyz/anchor";
 import { Approve } from "../target/types/approve";
 import {
+  type AccountMeta,
   Connection,
   Keypair,
   LAMPORTS_PER_SOL,
@@ -14,23 +15,34 @@ import {
 import {
   MINT_SIZE,
   TOKEN_2022_PROGRAM_ID,
-  type TOKEN_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  NATIVE_MINT,
+  NATIVE_MINT_2022,
   createAssociatedTokenAccountIdempotentInstruction,
   createInitializeMint2Instruction,
+  ExtensionType,
+  createInitializeNonTransferableMintInstruction,
   createMintToInstruction,
+  createRevokeInstruction,
+  getAccount,
   getAssociatedTokenAddressSync,
   getMinimumBalanceForRentExemptMint,
+  getMintLen,
 } from "@solana/spl-token";
-import { randomBytes } from "crypto";
+import { createHash, randomBytes } from "crypto";
 
 import { confirmTransaction, makeKeypairs } from "@solana-developers/helpers";
 
 import { createTokenAndMintTo, getTokenBalanceOn, transferSplTokenIx } from "./token.lib";
-import { areBnEqual, getRandomBigNumber } from "./utils.lib";
+import { areBnEqual } from "./utils.lib";
 
 const TOKEN_PROGRAM: typeof TOKEN_2022_PROGRAM_ID | typeof TOKEN_PROGRAM_ID =
   TOKEN_2022_PROGRAM_ID;
 
+type OfferMode = { delegation: {} } | { vault: {} };
+
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
 
 expect.addEqualityTesters([areBnEqual]);
 
@@ -51,7 +63,13 @@ describe("escrow with approve", () => {
 
     const program = anchor.workspace.Approve as Program<Approve>;
 
-    const [alice, bob, usdcMint, wifMint] = makeKeypairs(4);
+    // Only the upgrade authority, the provider wallet that deployed the program, can initialize it
+    const [programDataAddress] = PublicKey.findProgramAddressSync(
+        [program.programId.toBuffer()],
+        BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+    );
+
+    const [alice, bob, usdcMint, wifMint, treasury] = makeKeypairs(5);
 
     const [aliceUsdcAccount, aliceWifAccount, bobUsdcAccount, bobWifAccount] = [
         alice,
@@ -67,8 +85,8 @@ describe("escrow with approve", () => {
         )
     );
 
-    // Pick a random ID for the new offer.
-    const offerId = getRandomBigNumber();
+    // ID of the offer Alice makes in the first test, taken from her offer nonce.
+    let offerId: BN;
 
     // console.log('Alice: ', alice.publicKey.toBase58())
     // console.log('Bob: ', alice.publicKey.toBase58())
@@ -135,78 +153,286 @@ describe("escrow with approve", () => {
             usdcMint,
             wifMint,
         ]);
+
+        // Initialize the program, fees are disabled until a test enables them
+        const configTxSig = await program.methods
+            .initialize(0, treasury.publicKey)
+            .accounts({
+                admin: provider.publicKey,
+                programData: programDataAddress,
+            })
+            .rpc();
+        await confirmTransaction(connection, configTxSig);
     });    
 
 
+    /**
+     * Returns the id the next offer of the maker gets, the offer nonce of its
+     * maker state, which is created with the first offer
+     */
+    const getNextOfferId = async (maker: PublicKey): Promise<BN> => {
+        const [makerStateAddress] = PublicKey.findProgramAddressSync(
+            [Buffer.from("maker"), maker.toBuffer()],
+            program.programId
+        );
+        const makerState = await program.account.makerState.fetchNullable(makerStateAddress);
+        return makerState ? makerState.offerNonce : new BN(0);
+    };
+
     /**
      * Create an offer escrow transaction and confirm it
      * that make offer PDA account and approve usage of the offeredTokenMint token to offer PDA
      * @param maker 
-     * @param offerId 
      * @param offeredTokenMint 
      * @param offeredAmount 
      * @param wantedTokenMint 
      * @param wantedAmount 
+     * @param expiresAt unix timestamp, defaults to one hour from now
+     * @param mode `{ delegation: {} }` or `{ vault: {} }`
+     * @param allowedTaker the only wallet that can take the offer, `null` for public offers
+     * @param allowlistRoot Merkle root of the wallets that can take the offer, `null` for no allowlist
+     * @param market market whose order book lists the offer, `null` to not list it
      * @returns 
      * offerAddress: PublicKey
      */
     const makeOfferTx = async (
         maker: Keypair,
-        offerId: BN,
         offeredTokenMint: PublicKey,
         offeredAmount: BN,
         wantedTokenMint: PublicKey,
-        wantedAmount: BN
+        wantedAmount: BN,
+        expiresAt: BN = new BN(Math.floor(Date.now() / 1000) + 3600),
+        mode: OfferMode = { delegation: {} },
+        allowedTaker: PublicKey | null = null,
+        allowlistRoot: Array<number> | null = null,
+        market: PublicKey | null = null
       ): Promise<{
         offerAddress: PublicKey;
+        offerId: BN;
       }> => {
+            const offerId = await getNextOfferId(maker.publicKey);
+            // The `offer` address account is computed based
+            // on the other provided account addresses, and so we do not need to provide
+            // it explicitly in the `makeOffer()` account call below.  But we compute
+            // it here to derive the vault and return it for convenience.
+            const [offerAddress, _offerBump] = PublicKey.findProgramAddressSync(
+                [
+                    Buffer.from("offer"),
+                    maker.publicKey.toBuffer(),
+                    offerId.toArrayLike(Buffer, "le", 8),
+                ],
+                program.programId
+            );
+            const tokenProgramA = await getTokenProgramFor(offeredTokenMint);
+            const tokenProgramB = await getTokenProgramFor(wantedTokenMint);
+
             const transactionSignature = await program.methods
-                .makeOffer(offerId, offeredAmount, wantedAmount)
+                .makeOffer(offeredAmount, wantedAmount, expiresAt, mode, allowedTaker, allowlistRoot)
                 .accounts({
                     maker: maker.publicKey,
                     tokenMintA: offeredTokenMint,
                     tokenMintB: wantedTokenMint,
-                    // As the `token_program` account is specified as
+                    // As the `token_program_a` and `token_program_b` accounts are specified as
                     //
-                    //   pub token_program: Interface<'info, TokenInterface>,
+                    //   pub token_program_a: Interface<'info, TokenInterface>,
                     //
-                    // the client library needs us to provide the specific program address
-                    // explicitly.
+                    // the client library needs us to provide the specific program addresses
+                    // explicitly. Each leg uses the program that owns its mint.
                     //
                     // This is unlike the `associated_token_program` or the `system_program`
                     // account addresses, that are specified in the program IDL, as they are
                     // expected to reference the same programs for all the `makeOffer`
                     // invocations.
-                    tokenProgram: TOKEN_PROGRAM,
+                    tokenProgramA,
+                    tokenProgramB,
+                    //@ts-ignore
+                    vault: "vault" in mode ? getVaultAddress(offerAddress) : null,
+                    market,
                 })
                 .signers([maker])
                 .rpc();
     
             await confirmTransaction(connection, transactionSignature);
     
-            // The `offer` address account is computed based
-            // on the other provided account addresses, and so we do not need to provide
-            // them explicitly in the `makeOffer()` account call above.  But we compute
-            // them here and return for convenience.
-        
-            const [offerAddress, _offerBump] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("offer"),
-                    maker.publicKey.toBuffer(),
-                    offerId.toArrayLike(Buffer, "le", 8),
-                ],
-                program.programId
-            );
-    
-        return { offerAddress };
+        return { offerAddress, offerId };
     };
     
 
+    const updateOfferTx = async (
+        offerAddress: PublicKey,
+        maker: Keypair,
+        offeredTokenMint: PublicKey,
+        offeredAmount: BN,
+        wantedAmount: BN,
+      ): Promise<void> => {
+        const transactionSignature = await program.methods
+          .updateOffer(offeredAmount, wantedAmount)
+          .accounts({
+            maker: maker.publicKey,
+            tokenMintA: offeredTokenMint,
+            //@ts-ignore
+            offer: offerAddress,
+            vault: await getVaultFor(offerAddress),
+            market: await getMarketFor(offerAddress),
+            tokenProgram: TOKEN_PROGRAM,
+          })
+          .signers([maker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const updateFeeTx = async (feeBasisPoints: number): Promise<void> => {
+        const transactionSignature = await program.methods
+          .updateConfig(provider.publicKey, feeBasisPoints, treasury.publicKey)
+          .accounts({
+            admin: provider.publicKey,
+          })
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const setPausedTx = async (paused: boolean): Promise<void> => {
+        const transactionSignature = await program.methods
+          .setPaused(paused)
+          .accounts({
+            admin: provider.publicKey,
+          })
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    /**
+     * Returns the token program that owns the mint, SPL Token or Token-2022
+     */
+    const getTokenProgramFor = async (mint: PublicKey): Promise<PublicKey> =>
+        (await connection.getAccountInfo(mint))?.owner ?? TOKEN_PROGRAM;
+
+    /**
+     * Returns the token programs of both legs of an offer
+     */
+    const getTokenProgramsFor = async (
+        offerAddress: PublicKey
+      ): Promise<{ tokenProgramA: PublicKey; tokenProgramB: PublicKey }> => {
+        const offerAccount = await program.account.offer.fetchNullable(offerAddress);
+        if (!offerAccount) {
+            return { tokenProgramA: TOKEN_PROGRAM, tokenProgramB: TOKEN_PROGRAM };
+        }
+        return {
+            tokenProgramA: await getTokenProgramFor(offerAccount.tokenMintA),
+            tokenProgramB: await getTokenProgramFor(offerAccount.tokenMintB),
+        };
+    };
+
+    /**
+     * Returns the market an offer is listed on, or `null` for unlisted offers
+     * and offers that do not exist anymore
+     */
+    const getMarketFor = async (offerAddress: PublicKey): Promise<PublicKey | null> => {
+        const offerAccount = await program.account.offer.fetchNullable(offerAddress);
+        return offerAccount?.market ?? null;
+    };
+
+    /**
+     * Creates the market of a pair, the mints are sorted into base and quote
+     * @returns marketAddress: PublicKey
+     */
+    const createMarketTx = async (
+        mintA: PublicKey,
+        mintB: PublicKey,
+      ): Promise<{ marketAddress: PublicKey }> => {
+        const [baseMint, quoteMint] = Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) < 0
+            ? [mintA, mintB]
+            : [mintB, mintA];
+
+        const transactionSignature = await program.methods
+          .createMarket()
+          .accounts({
+            payer: provider.publicKey,
+            baseMint,
+            quoteMint,
+          })
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+
+        const [marketAddress] = PublicKey.findProgramAddressSync(
+            [Buffer.from("market"), baseMint.toBuffer(), quoteMint.toBuffer()],
+            program.programId
+        );
+        return { marketAddress };
+      };
+
+    /**
+     * Returns the address of the vault PDA token account of a vault mode offer
+     */
+    const getVaultAddress = (offerAddress: PublicKey): PublicKey => {
+        const [vaultAddress] = PublicKey.findProgramAddressSync(
+            [Buffer.from("vault"), offerAddress.toBuffer()],
+            program.programId
+        );
+        return vaultAddress;
+    };
+
+    /**
+     * Returns the vault of a vault mode offer, or `null` for delegation offers
+     * and offers that do not exist anymore
+     */
+    const getVaultFor = async (offerAddress: PublicKey): Promise<PublicKey | null> => {
+        const offerAccount = await program.account.offer.fetchNullable(offerAddress);
+        if (!offerAccount || !("vault" in offerAccount.mode)) {
+            return null;
+        }
+        return getVaultAddress(offerAddress);
+    };
+
+    /**
+     * Returns the token B accounts of the taker, maker and treasury, or `null`s
+     * for offers paid in SOL and offers that do not exist anymore
+     */
+    const getTokenBAccountsFor = async (
+        offerAddress: PublicKey,
+        taker: Keypair
+      ): Promise<{
+        takerTokenAccountB: PublicKey | null;
+        makerTokenAccountB: PublicKey | null;
+        treasuryTokenAccountB: PublicKey | null;
+      }> => {
+        const offerAccount = await program.account.offer.fetchNullable(offerAddress);
+        if (
+            !offerAccount ||
+            offerAccount.tokenMintB.equals(NATIVE_MINT) ||
+            offerAccount.tokenMintB.equals(NATIVE_MINT_2022)
+        ) {
+            return { takerTokenAccountB: null, makerTokenAccountB: null, treasuryTokenAccountB: null };
+        }
+        const tokenProgramB = await getTokenProgramFor(offerAccount.tokenMintB);
+        const [takerTokenAccountB, makerTokenAccountB, treasuryTokenAccountB] = [
+            taker.publicKey,
+            offerAccount.maker,
+            treasury.publicKey,
+        ].map((owner) =>
+            getAssociatedTokenAddressSync(offerAccount.tokenMintB, owner, false, tokenProgramB)
+        );
+        return { takerTokenAccountB, makerTokenAccountB, treasuryTokenAccountB };
+    };
+
     const takeOfferTx = async (
         offerAddress: PublicKey,
         makerAddress: PublicKey,
         taker: Keypair,
+        allowlistProof: Array<Array<number>> = [],
+        expectedTokenAAmount?: BN,
+        maxTokenBAmount?: BN,
+        // Extra account metas required by transfer hooks of token A and token B
+        transferHookAccounts: Array<AccountMeta> = [],
       ): Promise<void> => {
+        // By default the taker accepts exactly the current offer terms
+        const offerAccount = await program.account.offer.fetchNullable(offerAddress);
+        expectedTokenAAmount ??= offerAccount?.remainingA ?? new BN(0);
+        maxTokenBAmount ??= offerAccount?.remainingB ?? new BN(0);
     
         // `accounts` argument debugging tool.  Should be part of Anchor really.
         //
@@ -223,20 +449,331 @@ describe("escrow with approve", () => {
         // >;
     
         const transactionSignature = await program.methods
-          .takeOffer()
+          .takeOffer(expectedTokenAAmount, maxTokenBAmount, allowlistProof)
           .accounts({
             taker: taker.publicKey,
             //@ts-ignore
             offer: offerAddress,
-            // See note in the `makeOfferTx` on why this program address is provided
+            vault: await getVaultFor(offerAddress),
+            market: await getMarketFor(offerAddress),
+            treasury: treasury.publicKey,
+            ...(await getTokenBAccountsFor(offerAddress, taker)),
+            // See note in the `makeOfferTx` on why these program addresses are provided
             // and the rest are not.
-            tokenProgram: TOKEN_PROGRAM,
+            ...(await getTokenProgramsFor(offerAddress)),
           })
+          .remainingAccounts(transferHookAccounts)
           .signers([taker])
           .rpc();
     
         await confirmTransaction(connection, transactionSignature);
       };
+
+    /**
+     * Fully fills several offers of the same pair in one `takeOffers` instruction
+     */
+    const takeOffersTx = async (
+        offerAddresses: Array<PublicKey>,
+        taker: Keypair,
+        expectedTokenAAmount?: BN,
+        maxTokenBAmount?: BN,
+      ): Promise<void> => {
+        const offerAccounts = await Promise.all(
+            offerAddresses.map((offerAddress) => program.account.offer.fetch(offerAddress))
+        );
+        const { tokenMintA, tokenMintB } = offerAccounts[0];
+        const { tokenProgramA, tokenProgramB } = await getTokenProgramsFor(offerAddresses[0]);
+        expectedTokenAAmount ??= offerAccounts.reduce((sum, offer) => sum.add(offer.remainingA), new BN(0));
+        maxTokenBAmount ??= offerAccounts.reduce((sum, offer) => sum.add(offer.remainingB), new BN(0));
+
+        // Every offer: offer, maker, maker token accounts A and B, vault.
+        // Unused slots hold the program id.
+        const remainingAccounts: Array<AccountMeta> = [];
+        for (const [index, offer] of offerAccounts.entries()) {
+            const { makerTokenAccountB } = await getTokenBAccountsFor(offerAddresses[index], taker);
+            remainingAccounts.push(
+                { pubkey: offerAddresses[index], isSigner: false, isWritable: true },
+                { pubkey: offer.maker, isSigner: false, isWritable: true },
+                {
+                    pubkey: getAssociatedTokenAddressSync(tokenMintA, offer.maker, false, tokenProgramA),
+                    isSigner: false,
+                    isWritable: true,
+                },
+                { pubkey: makerTokenAccountB ?? program.programId, isSigner: false, isWritable: true },
+                {
+                    pubkey: (await getVaultFor(offerAddresses[index])) ?? program.programId,
+                    isSigner: false,
+                    isWritable: true,
+                }
+            );
+        }
+        const { takerTokenAccountB, treasuryTokenAccountB } = await getTokenBAccountsFor(
+            offerAddresses[0],
+            taker
+        );
+
+        const transactionSignature = await program.methods
+          .takeOffers(offerAddresses.length, expectedTokenAAmount, maxTokenBAmount, [])
+          .accounts({
+            taker: taker.publicKey,
+            tokenMintA,
+            tokenMintB,
+            //@ts-ignore
+            takerTokenAccountB,
+            treasury: treasury.publicKey,
+            treasuryTokenAccountB,
+            tokenProgramA,
+            tokenProgramB,
+            market: offerAccounts.find((offer) => offer.market)?.market ?? null,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([taker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const takeOfferPartialTx = async (
+        offerAddress: PublicKey,
+        taker: Keypair,
+        wantedAmount: BN,
+        expectedTokenAAmount: BN = new BN(0),
+      ): Promise<void> => {
+        const transactionSignature = await program.methods
+          .takeOfferPartial(wantedAmount, expectedTokenAAmount, [])
+          .accounts({
+            taker: taker.publicKey,
+            //@ts-ignore
+            offer: offerAddress,
+            vault: await getVaultFor(offerAddress),
+            market: await getMarketFor(offerAddress),
+            treasury: treasury.publicKey,
+            ...(await getTokenBAccountsFor(offerAddress, taker)),
+            ...(await getTokenProgramsFor(offerAddress)),
+          })
+          .signers([taker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const closeExpiredOfferTx = async (
+        offerAddress: PublicKey,
+        closer: Keypair,
+        offeredTokenMint: PublicKey,
+      ): Promise<void> => {
+        const transactionSignature = await program.methods
+          .closeExpiredOffer()
+          .accounts({
+            closer: closer.publicKey,
+            tokenMintA: offeredTokenMint,
+            //@ts-ignore
+            offer: offerAddress,
+            vault: await getVaultFor(offerAddress),
+            market: await getMarketFor(offerAddress),
+            tokenProgram: TOKEN_PROGRAM,
+          })
+          .signers([closer])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const cancelOfferTx = async (
+        offerAddress: PublicKey,
+        maker: Keypair,
+        offeredTokenMint: PublicKey,
+      ): Promise<void> => {
+        const transactionSignature = await program.methods
+          .cancelOffer()
+          .accounts({
+            maker: maker.publicKey,
+            tokenMintA: offeredTokenMint,
+            //@ts-ignore
+            offer: offerAddress,
+            vault: await getVaultFor(offerAddress),
+            market: await getMarketFor(offerAddress),
+            tokenProgram: TOKEN_PROGRAM,
+          })
+          .signers([maker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    type BasketLeg = { mint: PublicKey; amount: BN };
+
+    /**
+     * Returns the remaining account of a basket leg
+     */
+    const legAccount = (pubkey: PublicKey, isWritable: boolean = false): AccountMeta => ({
+        pubkey,
+        isSigner: false,
+        isWritable,
+    });
+
+    /**
+     * Returns the associated token account of `owner` for `mint` as a writable remaining account
+     */
+    const legTokenAccount = async (mint: PublicKey, owner: PublicKey): Promise<AccountMeta> =>
+        legAccount(
+            getAssociatedTokenAddressSync(mint, owner, true, await getTokenProgramFor(mint)),
+            true
+        );
+
+    /**
+     * Create a basket offer whose offered legs are delegated to the basket offer PDA
+     * @returns basketOfferAddress: PublicKey
+     */
+    const makeBasketOfferTx = async (
+        maker: Keypair,
+        offered: Array<BasketLeg>,
+        wanted: Array<BasketLeg>,
+        expiresAt: BN = new BN(Math.floor(Date.now() / 1000) + 3600),
+      ): Promise<{ basketOfferAddress: PublicKey }> => {
+        const basketOfferId = await getNextOfferId(maker.publicKey);
+        const [basketOfferAddress] = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("basket"),
+                maker.publicKey.toBuffer(),
+                basketOfferId.toArrayLike(Buffer, "le", 8),
+            ],
+            program.programId
+        );
+
+        // Offered legs: mint, maker token account, token program; then wanted legs: mint
+        const remainingAccounts: Array<AccountMeta> = [];
+        for (const { mint } of offered) {
+            remainingAccounts.push(
+                legAccount(mint),
+                await legTokenAccount(mint, maker.publicKey),
+                legAccount(await getTokenProgramFor(mint))
+            );
+        }
+        for (const { mint } of wanted) {
+            remainingAccounts.push(legAccount(mint));
+        }
+
+        const transactionSignature = await program.methods
+          .makeBasketOffer(offered, wanted, expiresAt)
+          .accounts({
+            maker: maker.publicKey,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([maker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+        return { basketOfferAddress };
+      };
+
+    const takeBasketOfferTx = async (
+        basketOfferAddress: PublicKey,
+        taker: Keypair,
+      ): Promise<void> => {
+        const basketOffer = await program.account.basketOffer.fetch(basketOfferAddress);
+
+        // Offered legs: mint, maker token account, taker token account, token program;
+        // then wanted legs: mint, taker, maker and treasury token accounts, token program
+        const remainingAccounts: Array<AccountMeta> = [];
+        for (const { mint } of basketOffer.offered) {
+            remainingAccounts.push(
+                legAccount(mint),
+                await legTokenAccount(mint, basketOffer.maker),
+                await legTokenAccount(mint, taker.publicKey),
+                legAccount(await getTokenProgramFor(mint))
+            );
+        }
+        for (const { mint } of basketOffer.wanted) {
+            remainingAccounts.push(
+                legAccount(mint),
+                await legTokenAccount(mint, taker.publicKey),
+                await legTokenAccount(mint, basketOffer.maker),
+                await legTokenAccount(mint, treasury.publicKey),
+                legAccount(await getTokenProgramFor(mint))
+            );
+        }
+
+        const transactionSignature = await program.methods
+          .takeBasketOffer()
+          .accounts({
+            taker: taker.publicKey,
+            maker: basketOffer.maker,
+            //@ts-ignore
+            basketOffer: basketOfferAddress,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([taker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    const cancelBasketOfferTx = async (
+        basketOfferAddress: PublicKey,
+        maker: Keypair,
+      ): Promise<void> => {
+        const basketOffer = await program.account.basketOffer.fetch(basketOfferAddress);
+
+        // Offered legs: maker token account, token program
+        const remainingAccounts: Array<AccountMeta> = [];
+        for (const { mint } of basketOffer.offered) {
+            remainingAccounts.push(
+                await legTokenAccount(mint, maker.publicKey),
+                legAccount(await getTokenProgramFor(mint))
+            );
+        }
+
+        const transactionSignature = await program.methods
+          .cancelBasketOffer()
+          .accounts({
+            maker: maker.publicKey,
+            //@ts-ignore
+            basketOffer: basketOfferAddress,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([maker])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
+
+    /**
+     * Closes an expired basket offer, the maker can also revoke its delegations
+     * by passing the accounts of the offered legs
+     */
+    const closeExpiredBasketOfferTx = async (
+        basketOfferAddress: PublicKey,
+        closer: Keypair,
+        revokeDelegations: boolean = false,
+      ): Promise<void> => {
+        const basketOffer = await program.account.basketOffer.fetch(basketOfferAddress);
+
+        // Offered legs: maker token account, token program
+        const remainingAccounts: Array<AccountMeta> = [];
+        if (revokeDelegations) {
+            for (const { mint } of basketOffer.offered) {
+                remainingAccounts.push(
+                    await legTokenAccount(mint, basketOffer.maker),
+                    legAccount(await getTokenProgramFor(mint))
+                );
+            }
+        }
+
+        const transactionSignature = await program.methods
+          .closeExpiredBasketOffer()
+          .accounts({
+            closer: closer.publicKey,
+            maker: basketOffer.maker,
+            //@ts-ignore
+            basketOffer: basketOfferAddress,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([closer])
+          .rpc();
+
+        await confirmTransaction(connection, transactionSignature);
+      };
     
 
 
@@ -250,14 +787,15 @@ describe("escrow with approve", () => {
 
         const getTokenBalance = getTokenBalanceOn(connection);
 
-        const { offerAddress } = await makeOfferTx(
+        const made = await makeOfferTx(
             alice,
-            offerId,
             usdcMint.publicKey,
             offeredUsdc,
             wifMint.publicKey,
             wantedWif
         );
+        const { offerAddress } = made;
+        offerId = made.offerId;
 
 
         expect(await getTokenBalance(aliceUsdcAccount)).toEqual(new BN(100_000_000));
@@ -315,8 +853,6 @@ describe("escrow with approve", () => {
      * 
      */
     test("Create offer, then spend tokens, and try to take offer, got error. Balances are not updated", async () => {
-        const offerId = getRandomBigNumber();
-
         const offeredUsdc = new BN(89_000_000);
         const wantedWif = new BN(100_000_000);
 
@@ -324,7 +860,6 @@ describe("escrow with approve", () => {
 
         const { offerAddress } = await makeOfferTx(
             alice,
-            offerId,
             usdcMint.publicKey,
             offeredUsdc,
             wifMint.publicKey,
@@ -351,6 +886,7 @@ describe("escrow with approve", () => {
         } catch (error) {
             // console.log(error.message);
             expect(error).toBeDefined();
+            expect(error.message).toContain("InsufficientMakerBalance");
         }
     
         expect(await getTokenBalance(aliceUsdcAccount)).toEqual(new BN(85_000_000));
@@ -362,4 +898,884 @@ describe("escrow with approve", () => {
     
     
 
-});
\ No newline at end of file
+
+    /**
+     * Tests that an offer can be cancelled by Alice, the delegation is revoked
+     * and the offer account is closed
+     */
+    test("Offer cancelled by Alice, delegation revoked and offer closed", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const balanceBefore = await getTokenBalance(aliceUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        let aliceUsdc = await getAccount(connection, aliceUsdcAccount, undefined, TOKEN_PROGRAM);
+        expect(aliceUsdc.delegate).toEqual(offerAddress);
+
+        await cancelOfferTx(offerAddress, alice, usdcMint.publicKey);
+
+        aliceUsdc = await getAccount(connection, aliceUsdcAccount, undefined, TOKEN_PROGRAM);
+        expect(aliceUsdc.delegate).toBeNull();
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(balanceBefore);
+
+        await expect(takeOfferTx(offerAddress, alice.publicKey, bob)).rejects.toThrow();
+    });
+
+
+    /**
+     * Tests that an offer can be filled by Bob in two parts and is closed
+     * only after the last part
+     */
+    test("Offer partially filled twice by Bob, closed when fully filled", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const bobWifBefore = await getTokenBalance(bobWifAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(3_000_000),
+            wifMint.publicKey,
+            new BN(9_000_000)
+        );
+
+        await takeOfferPartialTx(offerAddress, bob, new BN(3_000_000));
+
+        const offerAccount = await program.account.offer.fetch(offerAddress);
+        expect(offerAccount.remainingA).toEqual(new BN(2_000_000));
+        expect(offerAccount.remainingB).toEqual(new BN(6_000_000));
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(1_000_000)));
+
+        await takeOfferPartialTx(offerAddress, bob, new BN(6_000_000));
+
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(3_000_000)));
+        expect(await getTokenBalance(bobWifAccount)).toEqual(bobWifBefore.sub(new BN(9_000_000)));
+    });
+
+
+    /**
+     * Tests that an expired offer can not be taken and can be closed by anyone
+     */
+    test("Expired offer can not be taken and is closed by Bob", async () => {
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000),
+            new BN(Math.floor(Date.now() / 1000) + 2)
+        );
+
+        await new Promise((resolve) => setTimeout(resolve, 4_000));
+
+        await expect(takeOfferTx(offerAddress, alice.publicKey, bob)).rejects.toThrow();
+
+        await closeExpiredOfferTx(offerAddress, bob, usdcMint.publicKey);
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+
+    /**
+     * Tests that taking an offer whose delegation was revoked fails before
+     * Bob sends any tokens
+     */
+    test("Offer with revoked delegation can not be taken, Bob keeps his tokens", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        let tx = new Transaction();
+        tx.instructions = [
+            createRevokeInstruction(aliceUsdcAccount, alice.publicKey, [], TOKEN_PROGRAM),
+        ];
+        await provider.sendAndConfirm(tx, [alice]);
+
+        const bobWifBefore = await getTokenBalance(bobWifAccount);
+
+        await expect(takeOfferTx(offerAddress, alice.publicKey, bob)).rejects.toThrow(
+            "DelegateRevoked"
+        );
+
+        expect(await getTokenBalance(bobWifAccount)).toEqual(bobWifBefore);
+    });
+
+
+    /**
+     * Tests that a vault offer moves Alice's tokens into the vault and Bob is
+     * paid out from it
+     */
+    test("Vault offer created by Alice and taken by Bob", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000),
+            undefined,
+            { vault: {} }
+        );
+
+        const vault = getVaultAddress(offerAddress);
+        expect(await getTokenBalance(vault)).toEqual(new BN(1_000_000));
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(1_000_000)));
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(1_000_000)));
+        expect(await connection.getAccountInfo(vault)).toBeNull();
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+
+    /**
+     * Tests that sending SOL to the vault address of Alice's next offer ahead
+     * of time does not stop her from making the offer
+     */
+    test("Vault offer made after Bob funds its vault address", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const offerId = await getNextOfferId(alice.publicKey);
+        const [nextOfferAddress] = PublicKey.findProgramAddressSync(
+            [Buffer.from("offer"), alice.publicKey.toBuffer(), offerId.toArrayLike(Buffer, "le", 8)],
+            program.programId
+        );
+        const tx = new Transaction();
+        tx.instructions = [
+            SystemProgram.transfer({
+                fromPubkey: bob.publicKey,
+                toPubkey: getVaultAddress(nextOfferAddress),
+                lamports: LAMPORTS_PER_SOL / 100,
+            }),
+        ];
+        await provider.sendAndConfirm(tx, [bob]);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000),
+            undefined,
+            { vault: {} }
+        );
+
+        expect(offerAddress).toEqual(nextOfferAddress);
+        expect(await getTokenBalance(getVaultAddress(offerAddress))).toEqual(new BN(1_000_000));
+
+        await cancelOfferTx(offerAddress, alice, usdcMint.publicKey);
+    });
+
+
+    /**
+     * Tests that the protocol fee is taken from the token B leg and sent to
+     * the treasury
+     */
+    test("Protocol fee sent to the treasury when Bob takes an offer", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const aliceWifBefore = await getTokenBalance(aliceWifAccount);
+        const treasuryWifAccount = getAssociatedTokenAddressSync(
+            wifMint.publicKey,
+            treasury.publicKey,
+            false,
+            TOKEN_PROGRAM
+        );
+
+        await updateFeeTx(100);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000)
+        );
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+
+        expect(await getTokenBalance(treasuryWifAccount)).toEqual(new BN(20_000));
+        expect(await getTokenBalance(aliceWifAccount)).toEqual(aliceWifBefore.add(new BN(1_980_000)));
+
+        await updateFeeTx(0);
+    });
+
+
+    /**
+     * Tests that the program bootstrap can not run twice
+     */
+    test("Program can only be initialized once", async () => {
+        await expect(
+            program.methods
+                .initialize(0, treasury.publicKey)
+                .accounts({
+                    admin: provider.publicKey,
+                    programData: programDataAddress,
+                })
+                .rpc()
+        ).rejects.toThrow();
+    });
+
+
+    /**
+     * Tests that only the upgrade authority can initialize the program, so
+     * Alice can not claim the admin of a fresh deployment
+     */
+    test("Program can not be initialized by Alice", async () => {
+        await expect(
+            program.methods
+                .initialize(0, alice.publicKey)
+                .accounts({
+                    admin: alice.publicKey,
+                    programData: programDataAddress,
+                })
+                .signers([alice])
+                .rpc()
+        ).rejects.toThrow("NotUpgradeAuthority");
+    });
+
+
+    /**
+     * Tests that a paused program rejects new offers and takes but still
+     * lets Alice cancel
+     */
+    test("Paused program rejects offers, cancellation still works", async () => {
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        await setPausedTx(true);
+
+        await expect(
+            makeOfferTx(
+                alice,
+                usdcMint.publicKey,
+                new BN(1_000_000),
+                wifMint.publicKey,
+                new BN(1_000_000)
+            )
+        ).rejects.toThrow("ProgramPaused");
+        await expect(takeOfferTx(offerAddress, alice.publicKey, bob)).rejects.toThrow("ProgramPaused");
+
+        await cancelOfferTx(offerAddress, alice, usdcMint.publicKey);
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+
+        await setPausedTx(false);
+    });
+
+
+    /**
+     * Tests that making and taking an offer can be followed from the events
+     */
+    test("Offer lifecycle events emitted for make and take", async () => {
+        const madeEvents = [];
+        const takenEvents = [];
+        const madeListener = program.addEventListener("offerMade", (event) => madeEvents.push(event));
+        const takenListener = program.addEventListener("offerTaken", (event) => takenEvents.push(event));
+
+        const { offerAddress, offerId } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000)
+        );
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+
+        await new Promise((resolve) => setTimeout(resolve, 1_000));
+        await program.removeEventListener(madeListener);
+        await program.removeEventListener(takenListener);
+
+        expect(madeEvents).toHaveLength(1);
+        expect(madeEvents[0].offer).toEqual(offerAddress);
+        expect(madeEvents[0].id).toEqual(offerId);
+        expect(takenEvents).toHaveLength(1);
+        expect(takenEvents[0].taker).toEqual(bob.publicKey);
+        expect(takenEvents[0].tokenAAmount).toEqual(new BN(1_000_000));
+        expect(takenEvents[0].remainingB).toEqual(new BN(0));
+    });
+
+
+    /**
+     * Tests that offer ids come from the maker's nonce, so consecutive offers
+     * get consecutive ids and never collide
+     */
+    test("Consecutive offers of a maker get consecutive ids", async () => {
+        const first = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000)
+        );
+        await cancelOfferTx(first.offerAddress, alice, usdcMint.publicKey);
+
+        const second = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000)
+        );
+        await cancelOfferTx(second.offerAddress, alice, usdcMint.publicKey);
+
+        expect(second.offerId).toEqual(first.offerId.addn(1));
+        expect(second.offerAddress).not.toEqual(first.offerAddress);
+        expect(await getNextOfferId(alice.publicKey)).toEqual(second.offerId.addn(1));
+    });
+
+
+    /**
+     * Tests that a private offer can only be taken by the designated taker
+     */
+    test("Private offer for Bob can not be taken by another wallet", async () => {
+        const [carol] = makeKeypairs(1);
+
+        const carolWifAccount = getAssociatedTokenAddressSync(
+            wifMint.publicKey,
+            carol.publicKey,
+            false,
+            TOKEN_PROGRAM
+        );
+
+        // Fund Carol with SOL and some WIF from Bob, so only the offer check can fail
+        let tx = new Transaction();
+        tx.instructions = [
+            SystemProgram.transfer({
+                fromPubkey: provider.publicKey,
+                toPubkey: carol.publicKey,
+                lamports: LAMPORTS_PER_SOL,
+            }),
+            createAssociatedTokenAccountIdempotentInstruction(
+                provider.publicKey,
+                carolWifAccount,
+                carol.publicKey,
+                wifMint.publicKey,
+                TOKEN_PROGRAM
+            ),
+            await transferSplTokenIx(
+                connection,
+                bob,
+                wifMint.publicKey,
+                1_000_000,
+                6,
+                bobWifAccount,
+                carolWifAccount
+            ),
+        ];
+        await provider.sendAndConfirm(tx, [bob]);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000),
+            undefined,
+            undefined,
+            bob.publicKey
+        );
+
+        await expect(takeOfferTx(offerAddress, alice.publicKey, carol)).rejects.toThrow(
+            "TakerNotAllowed"
+        );
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+
+    /**
+     * Tests that an allowlist offer can be taken by a member with a valid
+     * Merkle proof and is rejected without one
+     */
+    test("Allowlist offer taken by Bob with a Merkle proof", async () => {
+        const sha256 = (...parts: Array<Buffer>) =>
+            createHash("sha256").update(Buffer.concat(parts)).digest();
+        const hashPair = (a: Buffer, b: Buffer) =>
+            Buffer.compare(a, b) <= 0 ? sha256(a, b) : sha256(b, a);
+
+        // Two members allowlist: Bob and a random wallet
+        const bobLeaf = sha256(bob.publicKey.toBuffer());
+        const otherLeaf = sha256(Keypair.generate().publicKey.toBuffer());
+        const root = hashPair(bobLeaf, otherLeaf);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000),
+            undefined,
+            undefined,
+            null,
+            Array.from(root)
+        );
+
+        await expect(takeOfferTx(offerAddress, alice.publicKey, bob)).rejects.toThrow("NotOnAllowlist");
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob, [Array.from(otherLeaf)]);
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+
+    /**
+     * Tests that Alice can reprice an open offer in place
+     */
+    test("Offer repriced by Alice keeps its address and delegation follows", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        await updateOfferTx(offerAddress, alice, usdcMint.publicKey, new BN(2_000_000), new BN(3_000_000));
+
+        const offerAccount = await program.account.offer.fetch(offerAddress);
+        expect(offerAccount.tokenAAmount).toEqual(new BN(2_000_000));
+        expect(offerAccount.tokenBWantedAmount).toEqual(new BN(3_000_000));
+        const aliceUsdc = await getAccount(connection, aliceUsdcAccount, undefined, TOKEN_PROGRAM);
+        expect(aliceUsdc.delegatedAmount).toEqual(BigInt(2_000_000));
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(2_000_000)));
+    });
+
+
+    /**
+     * Tests that Bob's take fails when the offer gives less than he expects
+     */
+    test("Take with worse terms than expected fails with slippage error", async () => {
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        await expect(
+            takeOfferTx(offerAddress, alice.publicKey, bob, [], new BN(1_000_001), new BN(1_000_000))
+        ).rejects.toThrow("SlippageExceeded");
+        await expect(
+            takeOfferTx(offerAddress, alice.publicKey, bob, [], new BN(1_000_000), new BN(999_999))
+        ).rejects.toThrow("SlippageExceeded");
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob, [], new BN(1_000_000), new BN(1_000_000));
+    });
+
+
+    /**
+     * Tests that offering a non-transferable Token-2022 mint is rejected
+     */
+    test("Offer of a non-transferable mint is rejected", async () => {
+        const [soulboundMint] = makeKeypairs(1);
+        const mintLen = getMintLen([ExtensionType.NonTransferable]);
+        const aliceSoulboundAccount = getAssociatedTokenAddressSync(
+            soulboundMint.publicKey,
+            alice.publicKey,
+            false,
+            TOKEN_PROGRAM
+        );
+
+        let tx = new Transaction();
+        tx.instructions = [
+            SystemProgram.createAccount({
+                fromPubkey: provider.publicKey,
+                newAccountPubkey: soulboundMint.publicKey,
+                lamports: await connection.getMinimumBalanceForRentExemption(mintLen),
+                space: mintLen,
+                programId: TOKEN_PROGRAM,
+            }),
+            createInitializeNonTransferableMintInstruction(soulboundMint.publicKey, TOKEN_PROGRAM),
+            createInitializeMint2Instruction(
+                soulboundMint.publicKey,
+                6,
+                provider.publicKey,
+                null,
+                TOKEN_PROGRAM
+            ),
+            createAssociatedTokenAccountIdempotentInstruction(
+                provider.publicKey,
+                aliceSoulboundAccount,
+                alice.publicKey,
+                soulboundMint.publicKey,
+                TOKEN_PROGRAM
+            ),
+            createMintToInstruction(
+                soulboundMint.publicKey,
+                aliceSoulboundAccount,
+                provider.publicKey,
+                1_000_000,
+                [],
+                TOKEN_PROGRAM
+            ),
+        ];
+        await provider.sendAndConfirm(tx, [soulboundMint]);
+
+        await expect(
+            makeOfferTx(
+                alice,
+                soulboundMint.publicKey,
+                new BN(1_000_000),
+                wifMint.publicKey,
+                new BN(1_000_000)
+            )
+        ).rejects.toThrow("NonTransferableMint");
+    });
+
+
+    /**
+     * Tests an offer of a Token-2022 mint for a legacy SPL Token mint
+     */
+    test("Offer of a Token-2022 mint for a legacy SPL token", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const [bonkMint] = makeKeypairs(1);
+        const [aliceBonkAccount, bobBonkAccount] = [alice, bob].map((owner) =>
+            getAssociatedTokenAddressSync(bonkMint.publicKey, owner.publicKey, false, TOKEN_PROGRAM_ID)
+        );
+
+        let tx = new Transaction();
+        tx.instructions = await createTokenAndMintTo(
+            connection,
+            provider.publicKey,
+            bonkMint.publicKey,
+            5,
+            provider.publicKey,
+            [{ recepient: bob.publicKey, amount: 10_000_000 }],
+            TOKEN_PROGRAM_ID
+        );
+        await provider.sendAndConfirm(tx, [bonkMint]);
+
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            bonkMint.publicKey,
+            new BN(2_000_000)
+        );
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(1_000_000)));
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(1_000_000)));
+        expect(await getTokenBalance(aliceBonkAccount)).toEqual(new BN(2_000_000));
+        expect(await getTokenBalance(bobBonkAccount)).toEqual(new BN(8_000_000));
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+
+    /**
+     * Tests an offer paid in SOL: Bob pays lamports without wrapping SOL
+     */
+    test("Offer of USDC for SOL taken by Bob in lamports", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            NATIVE_MINT,
+            new BN(LAMPORTS_PER_SOL / 2)
+        );
+        const offerRent = (await connection.getAccountInfo(offerAddress))!.lamports;
+        const aliceLamportsBefore = await connection.getBalance(alice.publicKey);
+
+        await takeOfferTx(offerAddress, alice.publicKey, bob);
+
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(1_000_000)));
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(1_000_000)));
+        // Alice receives the SOL and the rent of the closed offer
+        expect(await connection.getBalance(alice.publicKey)).toEqual(
+            aliceLamportsBefore + LAMPORTS_PER_SOL / 2 + offerRent
+        );
+        expect(await connection.getAccountInfo(offerAddress)).toBeNull();
+    });
+
+    /**
+     * Tests a basket offer of USDC and JUP for WIF and BONK taken by Bob in one transaction
+     */
+    test("Basket offer settles every leg atomically", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const [jupMint, bonkMint] = makeKeypairs(2);
+
+        // Token accounts are not created by the basket instructions, so create them upfront
+        let tx = new Transaction();
+        tx.instructions = [
+            ...(await createTokenAndMintTo(
+                connection,
+                provider.publicKey,
+                jupMint.publicKey,
+                6,
+                provider.publicKey,
+                [
+                    { recepient: alice.publicKey, amount: 3_000_000 },
+                    { recepient: bob.publicKey, amount: 0 },
+                ]
+            )),
+            ...(await createTokenAndMintTo(
+                connection,
+                provider.publicKey,
+                bonkMint.publicKey,
+                5,
+                provider.publicKey,
+                [
+                    { recepient: bob.publicKey, amount: 10_000_000 },
+                    { recepient: alice.publicKey, amount: 0 },
+                    { recepient: treasury.publicKey, amount: 0 },
+                ]
+            )),
+            createAssociatedTokenAccountIdempotentInstruction(
+                provider.publicKey,
+                getAssociatedTokenAddressSync(wifMint.publicKey, treasury.publicKey, false, TOKEN_PROGRAM),
+                treasury.publicKey,
+                wifMint.publicKey,
+                TOKEN_PROGRAM
+            ),
+        ];
+        await provider.sendAndConfirm(tx, [jupMint, bonkMint]);
+
+        const [aliceJupAccount, bobJupAccount, aliceBonkAccount, bobBonkAccount] = [
+            jupMint,
+            bonkMint,
+        ].flatMap((mint) =>
+            [alice, bob].map((owner) =>
+                getAssociatedTokenAddressSync(mint.publicKey, owner.publicKey, false, TOKEN_PROGRAM)
+            )
+        );
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const aliceWifBefore = await getTokenBalance(aliceWifAccount);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+        const bobWifBefore = await getTokenBalance(bobWifAccount);
+
+        const { basketOfferAddress } = await makeBasketOfferTx(
+            alice,
+            [
+                { mint: usdcMint.publicKey, amount: new BN(1_000_000) },
+                { mint: jupMint.publicKey, amount: new BN(2_000_000) },
+            ],
+            [
+                { mint: wifMint.publicKey, amount: new BN(3_000_000) },
+                { mint: bonkMint.publicKey, amount: new BN(4_000_000) },
+            ]
+        );
+        await takeBasketOfferTx(basketOfferAddress, bob);
+
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(1_000_000)));
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(1_000_000)));
+        expect(await getTokenBalance(aliceJupAccount)).toEqual(new BN(1_000_000));
+        expect(await getTokenBalance(bobJupAccount)).toEqual(new BN(2_000_000));
+        expect(await getTokenBalance(aliceWifAccount)).toEqual(aliceWifBefore.add(new BN(3_000_000)));
+        expect(await getTokenBalance(bobWifAccount)).toEqual(bobWifBefore.sub(new BN(3_000_000)));
+        expect(await getTokenBalance(aliceBonkAccount)).toEqual(new BN(4_000_000));
+        expect(await getTokenBalance(bobBonkAccount)).toEqual(new BN(6_000_000));
+        expect(await connection.getAccountInfo(basketOfferAddress)).toBeNull();
+    });
+
+    /**
+     * Tests that cancelling a basket offer closes it and revokes its delegations
+     */
+    test("Basket offer cancelled by Alice revokes its delegations", async () => {
+        const { basketOfferAddress } = await makeBasketOfferTx(
+            alice,
+            [{ mint: usdcMint.publicKey, amount: new BN(1_000_000) }],
+            [{ mint: wifMint.publicKey, amount: new BN(1_000_000) }]
+        );
+        await cancelBasketOfferTx(basketOfferAddress, alice);
+
+        expect(await connection.getAccountInfo(basketOfferAddress)).toBeNull();
+        const aliceUsdc = await getAccount(connection, aliceUsdcAccount, undefined, TOKEN_PROGRAM);
+        expect(aliceUsdc.delegate).toBeNull();
+    });
+
+    /**
+     * Tests that anyone can close an expired basket offer and its rent goes
+     * back to Alice
+     */
+    test("Expired basket offer can not be taken and is closed by Bob", async () => {
+        const { basketOfferAddress } = await makeBasketOfferTx(
+            alice,
+            [{ mint: usdcMint.publicKey, amount: new BN(1_000_000) }],
+            [{ mint: wifMint.publicKey, amount: new BN(1_000_000) }],
+            new BN(Math.floor(Date.now() / 1000) + 2)
+        );
+        await expect(closeExpiredBasketOfferTx(basketOfferAddress, bob)).rejects.toThrow("OfferNotExpired");
+        const aliceLamportsBefore = await connection.getBalance(alice.publicKey);
+
+        await new Promise((resolve) => setTimeout(resolve, 4_000));
+
+        await expect(takeBasketOfferTx(basketOfferAddress, bob)).rejects.toThrow();
+        await closeExpiredBasketOfferTx(basketOfferAddress, bob);
+
+        expect(await connection.getAccountInfo(basketOfferAddress)).toBeNull();
+        expect(await connection.getBalance(alice.publicKey)).toBeGreaterThan(aliceLamportsBefore);
+    });
+
+    /**
+     * Tests that Alice revokes the delegations of her expired basket offer
+     * when she closes it
+     */
+    test("Expired basket offer closed by Alice revokes its delegations", async () => {
+        const { basketOfferAddress } = await makeBasketOfferTx(
+            alice,
+            [{ mint: usdcMint.publicKey, amount: new BN(1_000_000) }],
+            [{ mint: wifMint.publicKey, amount: new BN(1_000_000) }],
+            new BN(Math.floor(Date.now() / 1000) + 2)
+        );
+
+        await new Promise((resolve) => setTimeout(resolve, 4_000));
+
+        await expect(closeExpiredBasketOfferTx(basketOfferAddress, bob, true)).rejects.toThrow("NotOfferMaker");
+        await closeExpiredBasketOfferTx(basketOfferAddress, alice, true);
+
+        expect(await connection.getAccountInfo(basketOfferAddress)).toBeNull();
+        const aliceUsdc = await getAccount(connection, aliceUsdcAccount, undefined, TOKEN_PROGRAM);
+        expect(aliceUsdc.delegate).toBeNull();
+    });
+
+    /**
+     * Tests that Bob fills a vault offer and a delegation offer of Alice in one instruction
+     */
+    test("Several offers of the same pair filled by Bob in one instruction", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const aliceUsdcBefore = await getTokenBalance(aliceUsdcAccount);
+        const aliceWifBefore = await getTokenBalance(aliceWifAccount);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+        const bobWifBefore = await getTokenBalance(bobWifAccount);
+
+        // A maker token account has a single delegate, so only one of the offers uses delegation
+        const { offerAddress: vaultOfferAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000),
+            undefined,
+            { vault: {} }
+        );
+        const { offerAddress: delegationOfferAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(2_000_000),
+            wifMint.publicKey,
+            new BN(3_000_000)
+        );
+
+        await takeOffersTx([vaultOfferAddress, delegationOfferAddress], bob);
+
+        expect(await getTokenBalance(aliceUsdcAccount)).toEqual(aliceUsdcBefore.sub(new BN(3_000_000)));
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore.add(new BN(3_000_000)));
+        expect(await getTokenBalance(aliceWifAccount)).toEqual(aliceWifBefore.add(new BN(5_000_000)));
+        expect(await getTokenBalance(bobWifAccount)).toEqual(bobWifBefore.sub(new BN(5_000_000)));
+        expect(await connection.getAccountInfo(vaultOfferAddress)).toBeNull();
+        expect(await connection.getAccountInfo(delegationOfferAddress)).toBeNull();
+    });
+
+    /**
+     * Tests that listing the same offer twice fails the whole batch
+     */
+    test("Batch take fails atomically when one offer is invalid", async () => {
+        const getTokenBalance = getTokenBalanceOn(connection);
+        const bobUsdcBefore = await getTokenBalance(bobUsdcAccount);
+
+        const { offerAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(1_000_000)
+        );
+
+        await expect(takeOffersTx([offerAddress, offerAddress], bob)).rejects.toThrow();
+
+        expect(await getTokenBalance(bobUsdcAccount)).toEqual(bobUsdcBefore);
+        expect(await connection.getAccountInfo(offerAddress)).not.toBeNull();
+
+        await cancelOfferTx(offerAddress, alice, usdcMint.publicKey);
+    });
+
+    /**
+     * Tests that the market order book lists offers by price and follows fills and cancels
+     */
+    test("Market order book keeps the best offers first", async () => {
+        const { marketAddress } = await createMarketTx(usdcMint.publicKey, wifMint.publicKey);
+        const usdcIsBase = Buffer.compare(usdcMint.publicKey.toBuffer(), wifMint.publicKey.toBuffer()) < 0;
+
+        // Both offers sell USDC for WIF, the second one at a better price for takers
+        const { offerAddress: expensiveOfferAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(3_000_000),
+            undefined,
+            { vault: {} },
+            null,
+            null,
+            marketAddress
+        );
+        const { offerAddress: cheapOfferAddress } = await makeOfferTx(
+            alice,
+            usdcMint.publicKey,
+            new BN(1_000_000),
+            wifMint.publicKey,
+            new BN(2_000_000),
+            undefined,
+            { vault: {} },
+            null,
+            null,
+            marketAddress
+        );
+
+        let market = await program.account.market.fetch(marketAddress);
+        // Selling the base mint is an ask (cheapest first), selling the quote mint is a bid (highest first)
+        const book = usdcIsBase ? market.asks : market.bids;
+        expect(book.map((entry) => entry.offer.toBase58())).toEqual([
+            cheapOfferAddress.toBase58(),
+            expensiveOfferAddress.toBase58(),
+        ]);
+
+        await takeOfferPartialTx(cheapOfferAddress, bob, new BN(1_000_000));
+        market = await program.account.market.fetch(marketAddress);
+        const bookAfterFill = usdcIsBase ? market.asks : market.bids;
+        expect(bookAfterFill[0].offer).toEqual(cheapOfferAddress);
+        expect(usdcIsBase ? bookAfterFill[0].baseAmount : bookAfterFill[0].quoteAmount).toEqual(
+            new BN(500_000)
+        );
+
+        await takeOfferTx(cheapOfferAddress, alice.publicKey, bob);
+        await cancelOfferTx(expensiveOfferAddress, alice, usdcMint.publicKey);
+        market = await program.account.market.fetch(marketAddress);
+        expect(market.asks).toEqual([]);
+        expect(market.bids).toEqual([]);
+    });
+
+});
@@ -35,6 +35,7 @@ const TOKEN_PROGRAM: typeof TOKEN_2022_PROGRAM_ID | typeof TOKEN_PROGRAM_ID =
  * @param {number} decimals - The number of decimal places for the token.
  * @param {PublicKey} mintAuthority - The public key of the account that will have authority over the mint.
  * @param {Array<{ recepient: PublicKey; amount: number }>} mintTo - An array of objects specifying the recipients and amounts of tokens to mint.
+ * @param {PublicKey} tokenProgram - The token program that owns the mint, Token-2022 by default.
  * @return {Promise<Array<TransactionInstruction>>} An array of transaction instructions to create the token mint and mint tokens.
  */
 export const createTokenAndMintTo = async (
@@ -43,7 +44,8 @@ export const createTokenAndMintTo = async (
     tokenMint: PublicKey,
     decimals: number,
     mintAuthority: PublicKey,
-    mintTo: Array<{ recepient: PublicKey; amount: number }>
+    mintTo: Array<{ recepient: PublicKey; amount: number }>,
+    tokenProgram: PublicKey = TOKEN_PROGRAM
   ): Promise<Array<TransactionInstruction>> => {
     let minimumLamports = await getMinimumBalanceForRentExemptMint(connection);
   
@@ -53,14 +55,14 @@ export const createTokenAndMintTo = async (
         newAccountPubkey: tokenMint,
         lamports: minimumLamports,
         space: MINT_SIZE,
-        programId: TOKEN_PROGRAM,
+        programId: tokenProgram,
       }),
       createInitializeMint2Instruction(
         tokenMint,
         decimals,
         mintAuthority,
         null,
-        TOKEN_PROGRAM
+        tokenProgram
       ),
     ];
   
@@ -69,7 +71,7 @@ export const createTokenAndMintTo = async (
         tokenMint,
         recepient,
         false,
-        TOKEN_PROGRAM
+        tokenProgram
       );
   
       return [
@@ -78,7 +80,7 @@ export const createTokenAndMintTo = async (
           ataAddress,
           recepient,
           tokenMint,
-          TOKEN_PROGRAM
+          tokenProgram
         ),
         createMintToInstruction(
           tokenMint,
@@ -86,7 +88,7 @@ export const createTokenAndMintTo = async (
           mintAuthority,
           amount,
           [],
-          TOKEN_PROGRAM
+          tokenProgram
         ),
       ];
     });